@@ -20,6 +20,10 @@ MEMORY
    after the vector table */
 /* _stext = ORIGIN(FLASH) + 0x400; */
 
+/* Flash sectors 1 and 2 are reserved for settings, see `main.rs`. Only the
+   vector table is left in sector 0, and the program starts at sector 3. */
+_stext = ORIGIN(FLASH) + 48K;
+
 /* Example of putting non-initialized variables into custom RAM locations. */
 /* This assumes you have defined a region RAM2 above, and in the Rust
    sources added the attribute `#[link_section = ".ram2bss"]` to the data
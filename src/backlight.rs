@@ -0,0 +1,50 @@
+use crate::display::Error;
+use embedded_hal::{digital::v2::OutputPin, PwmPin};
+
+/// Backlight of the display.
+pub trait Backlight {
+    /// Brightness is given in percent.
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Error>;
+}
+
+/// Backlight dimmed with PWM.
+pub struct PwmBacklight<P: PwmPin<Duty = u16>> {
+    pin: P,
+}
+
+impl<P: PwmPin<Duty = u16>> PwmBacklight<P> {
+    pub fn new(mut pin: P) -> Self {
+        pin.enable();
+        Self { pin }
+    }
+}
+
+impl<P: PwmPin<Duty = u16>> Backlight for PwmBacklight<P> {
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        let duty = self.pin.get_max_duty() as u32 * brightness.min(100) as u32 / 100;
+        self.pin.set_duty(duty as u16);
+        Ok(())
+    }
+}
+
+/// Backlight which can only be switched on and off, so it's not dimmed, only
+/// switched off.
+pub struct GpioBacklight<P: OutputPin> {
+    pin: P,
+}
+
+impl<P: OutputPin> GpioBacklight<P> {
+    pub fn new(pin: P) -> Self {
+        Self { pin }
+    }
+}
+
+impl<P: OutputPin> Backlight for GpioBacklight<P> {
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        if brightness > 0 {
+            self.pin.set_high().map_err(|_| Error)
+        } else {
+            self.pin.set_low().map_err(|_| Error)
+        }
+    }
+}
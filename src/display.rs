@@ -0,0 +1,265 @@
+use crate::{
+    glyph,
+    hd44780::{DataBus, DisplayMode, HD44780},
+};
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+// Size of the biggest supported display.
+const MAX_ROWS: usize = 4;
+const MAX_COLUMNS: usize = 40;
+
+/// Size of the display in characters.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Geometry {
+    Lcd16x2,
+    Lcd16x4,
+    Lcd20x4,
+    Lcd40x2,
+}
+
+impl Geometry {
+    pub fn rows(self) -> usize {
+        match self {
+            Geometry::Lcd16x2 | Geometry::Lcd40x2 => 2,
+            Geometry::Lcd16x4 | Geometry::Lcd20x4 => 4,
+        }
+    }
+
+    pub fn columns(self) -> usize {
+        match self {
+            Geometry::Lcd16x2 | Geometry::Lcd16x4 => 16,
+            Geometry::Lcd20x4 => 20,
+            Geometry::Lcd40x2 => 40,
+        }
+    }
+}
+
+/// Text contents of the display.
+#[derive(Clone)]
+pub struct Buffer {
+    geometry: Geometry,
+    cells: [[char; MAX_COLUMNS]; MAX_ROWS],
+}
+
+impl Buffer {
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry,
+            cells: [[' '; MAX_COLUMNS]; MAX_ROWS],
+        }
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    pub fn rows(&self) -> usize {
+        self.geometry.rows()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.geometry.columns()
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> char {
+        self.cells[row][column]
+    }
+
+    /// Characters outside of the display are ignored.
+    pub fn set(&mut self, row: usize, column: usize, character: char) {
+        if row < self.rows() && column < self.columns() {
+            self.cells[row][column] = character;
+        }
+    }
+
+    /// Writes text starting at the given cell. Text that doesn't fit in the
+    /// row is cut off.
+    pub fn write_str(&mut self, row: usize, column: usize, text: &str) {
+        for (offset, character) in text.chars().enumerate() {
+            self.set(row, column + offset, character);
+        }
+    }
+}
+
+/// Display on which frames are rendered. Frames are rendered into a text
+/// buffer first, so the backend only has to know how to show characters.
+pub trait DisplayBackend {
+    /// Shows contents of the buffer. Implementations should send to the
+    /// display only characters that changed since previous call.
+    fn draw(
+        &mut self,
+        buffer: &Buffer,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error>;
+}
+
+/// Error of the display hardware, shared by the backends, the HD44780 driver
+/// and the backlight. Pin and bus errors differ for every type and they are
+/// not recoverable anyway, so they are all flattened into this one.
+#[derive(Debug)]
+pub struct Error;
+
+// Number of custom characters HD44780 can hold in its CGRAM.
+const CGRAM_SLOTS: usize = 8;
+
+/// HD44780 character LCD, connected either directly or through an I2C
+/// backpack.
+pub struct Hd44780Display<B: DataBus> {
+    hd44780: HD44780<B>,
+
+    // Character uploaded to each of CGRAM slots.
+    slots: [Option<char>; CGRAM_SLOTS],
+    // Bit mask of slots used by currently drawn buffer. These can't be
+    // overwritten until the buffer is displayed.
+    used_slots: u8,
+    // Contents of the display, so only changed characters have to be sent.
+    shadow: [[u8; MAX_COLUMNS]; MAX_ROWS],
+}
+
+impl<B: DataBus> Hd44780Display<B> {
+    pub fn new(bus: B, delay: &mut (impl DelayMs<u8> + DelayUs<u16>)) -> Result<Self, Error> {
+        let mut hd44780 = HD44780::new(bus, delay)?;
+
+        hd44780.set_display_mode(
+            DisplayMode {
+                display: true,
+                cursor: false,
+                cursor_blink: false,
+            },
+            delay,
+        )?;
+
+        Ok(Self {
+            hd44780,
+
+            slots: [None; CGRAM_SLOTS],
+            used_slots: 0,
+            // Display is cleared during initialization.
+            shadow: [[b' '; MAX_COLUMNS]; MAX_ROWS],
+        })
+    }
+
+    fn set_cursor(
+        &mut self,
+        geometry: Geometry,
+        row: usize,
+        column: usize,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.hd44780
+            .set_cursor_pos(ddram_address(geometry, row, column), delay)
+    }
+
+    fn encode(
+        &mut self,
+        character: char,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<u8, Error> {
+        if character.is_ascii() {
+            return Ok(character as u8);
+        }
+
+        if let Some(code) = glyph::rom_code(character) {
+            return Ok(code);
+        }
+
+        let glyph = match glyph::find(character) {
+            Some(glyph) => glyph,
+            None => return Ok(b'?'),
+        };
+
+        let slot = match self.slots.iter().position(|slot| *slot == Some(character)) {
+            Some(slot) => slot,
+            None => {
+                // Prefer empty slots, so glyphs of previous frames stay
+                // uploaded as long as possible.
+                let free_slot = (0..CGRAM_SLOTS)
+                    .find(|slot| self.slots[*slot].is_none())
+                    .or_else(|| (0..CGRAM_SLOTS).find(|slot| self.used_slots & (1 << slot) == 0));
+
+                match free_slot {
+                    Some(slot) => {
+                        self.hd44780
+                            .set_custom_char(slot as u8, &glyph.pattern, delay)?;
+                        self.slots[slot] = Some(character);
+                        slot
+                    }
+                    None => return Ok(glyph.fallback),
+                }
+            }
+        };
+
+        self.used_slots |= 1 << slot;
+        Ok(slot as u8)
+    }
+}
+
+impl<B: DataBus> DisplayBackend for Hd44780Display<B> {
+    /// Characters missing in the ROM are uploaded to CGRAM before anything
+    /// is written, as writing to CGRAM moves the cursor. Then cursor is set
+    /// only at the beginning of every run of changed characters, as it moves
+    /// right after every write.
+    fn draw(
+        &mut self,
+        buffer: &Buffer,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        let mut bytes = [[b' '; MAX_COLUMNS]; MAX_ROWS];
+        self.used_slots = 0;
+        for (row, line) in bytes.iter_mut().enumerate().take(buffer.rows()) {
+            for (column, byte) in line.iter_mut().enumerate().take(buffer.columns()) {
+                *byte = self.encode(buffer.get(row, column), delay)?;
+            }
+        }
+
+        for (row, line) in bytes.iter().enumerate().take(buffer.rows()) {
+            let mut cursor = None;
+            for (column, &byte) in line.iter().enumerate().take(buffer.columns()) {
+                if self.shadow[row][column] == byte {
+                    continue;
+                }
+
+                if cursor != Some(column) {
+                    self.set_cursor(buffer.geometry(), row, column, delay)?;
+                }
+                self.hd44780.write_byte(byte, delay)?;
+                self.shadow[row][column] = byte;
+                cursor = Some(column + 1);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// In two-line mode DDRAM of the controller holds two lines of 40
+/// characters, starting at 0x00 and 0x40. Displays with four rows split each
+/// of them in two, so third and fourth rows continue the first and the
+/// second.
+fn ddram_address(geometry: Geometry, row: usize, column: usize) -> u8 {
+    ((row % 2) * 0x40 + (row / 2) * geometry.columns() + column) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_rows_start_at_both_ddram_lines() {
+        assert_eq!(ddram_address(Geometry::Lcd16x2, 0, 0), 0x00);
+        assert_eq!(ddram_address(Geometry::Lcd16x2, 0, 15), 0x0f);
+        assert_eq!(ddram_address(Geometry::Lcd16x2, 1, 0), 0x40);
+        assert_eq!(ddram_address(Geometry::Lcd16x2, 1, 15), 0x4f);
+    }
+
+    #[test]
+    fn four_rows_continue_the_first_two() {
+        assert_eq!(ddram_address(Geometry::Lcd20x4, 0, 0), 0x00);
+        assert_eq!(ddram_address(Geometry::Lcd20x4, 1, 0), 0x40);
+        assert_eq!(ddram_address(Geometry::Lcd20x4, 2, 0), 0x14);
+        assert_eq!(ddram_address(Geometry::Lcd20x4, 3, 0), 0x54);
+        assert_eq!(ddram_address(Geometry::Lcd20x4, 3, 19), 0x67);
+        assert_eq!(ddram_address(Geometry::Lcd16x4, 2, 0), 0x10);
+        assert_eq!(ddram_address(Geometry::Lcd16x4, 3, 0), 0x50);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::locale::Strings;
+
+/// Unrecoverable error of one of the peripherals or of the motion. Motor is
+/// stopped until the operator acknowledges it, and then the lift is homed
+/// again, as its position can't be trusted anymore.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Fault {
+    Encoder,
+    FineEncoder,
+    FootPedal,
+    LimitSwitch,
+    Motor,
+    Pendant,
+    Display,
+    /// Limit switch didn't trigger in time while homing.
+    HomingTimeout,
+    /// Limit switch didn't trigger within the maximum travel while homing.
+    HomingTravel,
+    /// Lift hit the limit switch outside homing, so steps were lost.
+    LowerLimit,
+    UpperLimit,
+    /// Settings couldn't be saved in the flash.
+    Storage,
+    /// Touch plate input couldn't be read.
+    Probe,
+    /// Bit touched the plate before probing started.
+    ProbeTouching,
+    /// Bit didn't touch the plate within the maximum probing distance.
+    ProbeDistance,
+    /// Encoder button couldn't be read.
+    Button,
+}
+
+impl Fault {
+    /// Code shown on the screen, so it can be looked up in the manual.
+    pub fn code(self) -> u8 {
+        match self {
+            Fault::Encoder => 1,
+            Fault::FineEncoder => 2,
+            Fault::FootPedal => 3,
+            Fault::LimitSwitch => 4,
+            Fault::Motor => 5,
+            Fault::Pendant => 6,
+            Fault::Display => 7,
+            Fault::HomingTimeout => 8,
+            Fault::HomingTravel => 9,
+            Fault::LowerLimit => 10,
+            Fault::UpperLimit => 11,
+            Fault::Storage => 12,
+            Fault::Probe => 13,
+            Fault::ProbeTouching => 14,
+            Fault::ProbeDistance => 15,
+            Fault::Button => 16,
+        }
+    }
+
+    pub fn message(self, strings: &Strings) -> &'static str {
+        match self {
+            Fault::Encoder => strings.encoder_fault,
+            Fault::FineEncoder => strings.fine_encoder_fault,
+            Fault::FootPedal => strings.foot_pedal_fault,
+            Fault::LimitSwitch => strings.limit_switch_fault,
+            Fault::Motor => strings.motor_fault,
+            Fault::Pendant => strings.pendant_fault,
+            Fault::Display => strings.display_fault,
+            Fault::HomingTimeout => strings.homing_timeout_fault,
+            Fault::HomingTravel => strings.homing_travel_fault,
+            Fault::LowerLimit => strings.lower_limit_fault,
+            Fault::UpperLimit => strings.upper_limit_fault,
+            Fault::Storage => strings.storage_fault,
+            Fault::Probe => strings.probe_fault,
+            Fault::ProbeTouching => strings.probe_touching_fault,
+            Fault::ProbeDistance => strings.probe_distance_fault,
+            Fault::Button => strings.button_fault,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use embedded_hal::digital::v2::InputPin;
+
+pub struct FootPedal<UP: InputPin, DOWN: InputPin> {
+    pub up: UP,
+    pub down: DOWN,
+}
+
+impl<UP: InputPin, DOWN: InputPin> FootPedal<UP, DOWN> {
+    pub fn new(up: UP, down: DOWN) -> Self {
+        Self { up, down }
+    }
+
+    /// Returns direction in which pedal is held. When both pedals are pressed
+    /// at once, it's treated as if none of them was pressed.
+    pub fn direction(&self) -> Result<Option<Direction>, Error<UP, DOWN>> {
+        let up = self.up.is_high().map_err(|err| Error::Up(err))?;
+        let down = self.down.is_high().map_err(|err| Error::Down(err))?;
+
+        match (up, down) {
+            (true, false) => Ok(Some(Direction::Up)),
+            (false, true) => Ok(Some(Direction::Down)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Both pedals pressed at once are used in button combinations.
+    pub fn both_pressed(&self) -> Result<bool, Error<UP, DOWN>> {
+        let up = self.up.is_high().map_err(|err| Error::Up(err))?;
+        let down = self.down.is_high().map_err(|err| Error::Down(err))?;
+
+        Ok(up && down)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+pub enum Error<UP: InputPin, DOWN: InputPin> {
+    Up(UP::Error),
+    Down(DOWN::Error),
+}
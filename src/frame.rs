@@ -0,0 +1,403 @@
+use crate::{
+    fault::Fault,
+    locale::Strings,
+    menu::{Coordinates, PresetChoice, Value},
+    render::{Canvas, Render},
+    unit::{self, Unit},
+};
+use arrayvec::ArrayString;
+use core::fmt::{self, Write};
+
+/// Frames built into the mill.
+pub enum Frame {
+    /// Height in micrometres, with the jog increment of the encoder in
+    /// hundredths of a millimetre. Increment is shown in millimetres in every
+    /// unit. Status is shown only on displays with more rows.
+    Height {
+        height: i32,
+        jog_increment: u32,
+        status: Status,
+    },
+    /// Height in micrometres, drawn with digits two rows tall. Jog increment
+    /// and status are shown only on displays with more rows.
+    BigHeight {
+        height: i32,
+        jog_increment: u32,
+        status: Status,
+    },
+    /// Lift in motion. All heights are in micrometres. Status is shown only
+    /// on displays with more rows.
+    Moving {
+        start: i32,
+        current: i32,
+        target: i32,
+        status: Status,
+    },
+    /// Selected entry of the settings menu. Value is shown only for
+    /// settings and presets, with arrows while it's edited.
+    Menu {
+        label: &'static str,
+        value: Option<Value>,
+        editing: bool,
+    },
+    /// Question whether to resume the height in micrometres saved before the
+    /// power was switched off, or to home the lift.
+    Resume {
+        height: i32,
+        resume: bool,
+    },
+    /// Raw state of the inputs and the motor.
+    Diagnostics(Diagnostics),
+    /// Error code and message. Displays with more rows also tell how to
+    /// acknowledge it.
+    Fault(Fault),
+    Calibrating,
+    Probing,
+    Welcome,
+}
+
+impl Render for Frame {
+    fn render(&self, canvas: &mut Canvas) -> Result<(), fmt::Error> {
+        let strings = canvas.strings();
+        let columns = canvas.columns();
+        let tall = canvas.is_tall();
+
+        match self {
+            Frame::Height {
+                height,
+                jog_increment,
+                status,
+            } => {
+                // Two rows leave no room for the label next to the increment.
+                if tall {
+                    canvas.write_str(0, 0, strings.current_height);
+                    write_status(canvas, 2, status);
+                    write_jog_increment(canvas, 3, *jog_increment)?;
+                } else {
+                    write_jog_increment(canvas, 0, *jog_increment)?;
+                }
+                canvas.write_height(1, *height)?;
+            }
+            Frame::BigHeight {
+                height,
+                jog_increment,
+                status,
+            } => {
+                if tall {
+                    write_status(canvas, 0, status);
+                    canvas.write_big_height(1, *height)?;
+                    write_jog_increment(canvas, 3, *jog_increment)?;
+                } else {
+                    canvas.write_big_height(0, *height)?;
+                }
+            }
+            Frame::Moving {
+                start,
+                current,
+                target,
+                status,
+            } => {
+                let arrow = if target > current { "↑" } else { "↓" };
+                let mut unit = canvas.unit();
+                let mut current_text = unit::format_height(*current, unit)?;
+                let mut target_text = unit::format_height(*target, unit)?;
+                let target_row = if tall { 1 } else { 0 };
+
+                // Both heights share the first row on two rows, so they drop
+                // decimal places until they fit next to each other.
+                while !tall && current_text.len() + target_text.len() + 2 > columns {
+                    match unit.coarser() {
+                        Some(coarser) => unit = coarser,
+                        None => break,
+                    }
+                    current_text = unit::format_height(*current, unit)?;
+                    target_text = unit::format_height(*target, unit)?;
+                }
+
+                canvas.write_str(0, 0, arrow);
+                canvas.write_str(0, 1, &current_text);
+                canvas.write_right_aligned(target_row, &target_text);
+                if tall {
+                    write_status(canvas, 2, status);
+                }
+                let progress_row = canvas.rows() - 1;
+                canvas.write_progress_bar(progress_row, progress(*start, *current, *target));
+            }
+            Frame::Menu {
+                label,
+                value,
+                editing,
+            } => {
+                canvas.write_str(0, 0, label);
+                if let Some(value) = value {
+                    canvas.write_centered(1, &format_value(*value, strings, canvas.unit())?);
+                }
+                if *editing {
+                    canvas.set(1, 0, '←');
+                    canvas.set(1, columns - 1, '→');
+                }
+            }
+            Frame::Fault(fault) => {
+                let mut title = ArrayString::<[_; 16]>::new();
+                write!(title, "{} E{:02}", strings.fault, fault.code())?;
+                canvas.write_str(0, 0, &title);
+                canvas.write_str(1, 0, fault.message(strings));
+                if tall {
+                    canvas.write_str(3, 0, strings.acknowledge);
+                }
+            }
+            Frame::Resume { height, resume } => {
+                let mut question = ArrayString::<[_; 32]>::new();
+                write!(
+                    question,
+                    "{} {}?",
+                    strings.resume_at,
+                    unit::format_height(*height, canvas.unit())?
+                )?;
+                canvas.write_centered(0, &question);
+
+                let choice = if *resume { strings.yes } else { strings.rehome };
+                canvas.write_centered(1, choice);
+                canvas.set(1, 0, '←');
+                canvas.set(1, columns - 1, '→');
+            }
+            Frame::Diagnostics(diagnostics) => {
+                let mut levels = ArrayString::<[_; 16]>::new();
+                write!(
+                    levels,
+                    "A{} B{} H{} L{}{} M{}",
+                    level(diagnostics.sia),
+                    level(diagnostics.sib),
+                    level(diagnostics.home),
+                    level(diagnostics.lower_limit),
+                    level(diagnostics.upper_limit),
+                    level(Some(diagnostics.motor_enabled)),
+                )?;
+                canvas.write_str(0, 0, &levels);
+
+                let mut steps = ArrayString::<[_; 24]>::new();
+                match diagnostics.current_height {
+                    Some(current_height) => write!(steps, "{}", current_height)?,
+                    None => steps.push('-'),
+                }
+                write!(steps, ">{}", diagnostics.target_height)?;
+                canvas.write_str(1, 0, &steps);
+
+                let mut fault = ArrayString::<[_; 4]>::new();
+                match diagnostics.last_fault {
+                    Some(last_fault) => write!(fault, "E{:02}", last_fault.code())?,
+                    None => fault.push_str("E--"),
+                }
+                canvas.write_right_aligned(1, &fault);
+            }
+            Frame::Calibrating => {
+                canvas.write_str(0, 0, strings.calibrating);
+            }
+            Frame::Probing => {
+                canvas.write_str(0, 0, strings.probing);
+            }
+            Frame::Welcome => {
+                canvas.write_str(0, 0, strings.welcome);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// State seen by the firmware, shown on the diagnostics screen.
+pub struct Diagnostics {
+    // Levels of the input pins, `None` when they couldn't be read.
+    pub sia: Option<bool>,
+    pub sib: Option<bool>,
+    pub home: Option<bool>,
+    pub lower_limit: Option<bool>,
+    pub upper_limit: Option<bool>,
+    pub motor_enabled: bool,
+    // Heights in motor steps.
+    pub current_height: Option<u32>,
+    pub target_height: u32,
+    pub last_fault: Option<Fault>,
+}
+
+/// Coordinates of the shown heights, and the preset at the target height.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Status {
+    pub coordinates: Coordinates,
+    // Name of the preset, `None` when the target isn't at any.
+    pub preset: Option<&'static str>,
+}
+
+/// Writes the coordinates on the left and the preset on the right, if it
+/// fits next to them.
+fn write_status(canvas: &mut Canvas, row: usize, status: &Status) {
+    let strings = canvas.strings();
+    let coordinates = match status.coordinates {
+        Coordinates::Machine => strings.machine,
+        Coordinates::Work => strings.work,
+    };
+    canvas.write_str(row, 0, coordinates);
+
+    if let Some(preset) = status.preset {
+        if coordinates.chars().count() + preset.chars().count() < canvas.columns() {
+            canvas.write_right_aligned(row, preset);
+        }
+    }
+}
+
+fn write_jog_increment(canvas: &mut Canvas, row: usize, hundredths: u32) -> fmt::Result {
+    let mut content = ArrayString::<[_; 24]>::new();
+    write!(
+        content,
+        "{} {}",
+        canvas.strings().jog_increment,
+        unit::format_jog_increment(hundredths)?
+    )?;
+    canvas.write_str(row, 0, &content);
+    Ok(())
+}
+
+/// Level of an input pin, or `?` when it couldn't be read.
+fn level(level: Option<bool>) -> char {
+    match level {
+        Some(true) => '1',
+        Some(false) => '0',
+        None => '?',
+    }
+}
+
+fn format_value(
+    value: Value,
+    strings: &Strings,
+    unit: Unit,
+) -> Result<ArrayString<[u8; 16]>, fmt::Error> {
+    let mut content = ArrayString::<[_; 16]>::new();
+
+    match value {
+        Value::Number(number) => write!(content, "{}", number)?,
+        Value::Milimetres(milimetres) => write!(content, "{}mm", milimetres)?,
+        Value::Language(language) => content
+            .try_push_str(language.name())
+            .map_err(|_| fmt::Error)?,
+        Value::Unit(unit) => content = unit::format_resolution(unit)?,
+        Value::Height(Some(height)) => content = unit::format_height(height, unit)?,
+        Value::Height(None) => content.push_str("---"),
+        Value::PresetChoice(PresetChoice::Recall) => content
+            .try_push_str(strings.recall)
+            .map_err(|_| fmt::Error)?,
+        Value::PresetChoice(PresetChoice::Save) => {
+            content.try_push_str(strings.save).map_err(|_| fmt::Error)?
+        }
+        Value::Coordinates(Coordinates::Machine) => content
+            .try_push_str(strings.machine)
+            .map_err(|_| fmt::Error)?,
+        Value::Coordinates(Coordinates::Work) => {
+            content.try_push_str(strings.work).map_err(|_| fmt::Error)?
+        }
+    }
+
+    Ok(content)
+}
+
+/// Returns how much of the way from start to target was already made, in
+/// permille.
+fn progress(start: i32, current: i32, target: i32) -> u32 {
+    let distance = (target as i64 - start as i64).abs();
+    let made = (current as i64 - start as i64).abs();
+
+    if distance == 0 {
+        1000
+    } else {
+        (made * 1000 / distance).min(1000) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        display::{Buffer, Geometry},
+        locale::Language,
+    };
+
+    const STATUS: Status = Status {
+        coordinates: Coordinates::Machine,
+        preset: Some("Preset 1"),
+    };
+
+    fn render(frame: Frame, geometry: Geometry) -> Buffer {
+        let mut buffer = Buffer::new(geometry);
+        let unit = Unit::Millimetres { decimals: 2 };
+        frame
+            .render(&mut Canvas::new(&mut buffer, Language::English, unit))
+            .unwrap();
+        buffer
+    }
+
+    fn row(buffer: &Buffer, row: usize) -> ArrayString<[u8; 160]> {
+        let mut text = ArrayString::new();
+        for column in 0..buffer.columns() {
+            text.push(buffer.get(row, column));
+        }
+        text
+    }
+
+    #[test]
+    fn moving_heights_drop_decimals_to_share_a_row() {
+        let frame = Frame::Moving {
+            start: 100_000,
+            current: 100_000,
+            target: 123_450,
+            status: STATUS,
+        };
+        let buffer = render(frame, Geometry::Lcd16x2);
+
+        assert_eq!(row(&buffer, 0).as_str(), "↑100.0mm 123.5mm");
+    }
+
+    #[test]
+    fn moving_heights_keep_decimals_on_their_own_rows() {
+        let frame = Frame::Moving {
+            start: 100_000,
+            current: 100_000,
+            target: 123_450,
+            status: STATUS,
+        };
+        let buffer = render(frame, Geometry::Lcd16x4);
+
+        assert_eq!(row(&buffer, 0).as_str(), "↑100.00mm       ");
+        assert_eq!(row(&buffer, 1).as_str(), "        123.45mm");
+        assert_eq!(row(&buffer, 2).as_str(), "Machine Preset 1");
+    }
+
+    #[test]
+    fn tall_height_shows_status_above_increment() {
+        let frame = Frame::Height {
+            height: 12_340,
+            jog_increment: 10,
+            status: Status {
+                coordinates: Coordinates::Work,
+                preset: None,
+            },
+        };
+        let buffer = render(frame, Geometry::Lcd20x4);
+
+        assert_eq!(row(&buffer, 2).as_str(), "Work                ");
+        assert_eq!(row(&buffer, 3).as_str(), "Step: 0.1mm         ");
+    }
+
+    #[test]
+    fn preset_is_left_out_when_it_does_not_fit() {
+        let frame = Frame::Height {
+            height: 0,
+            jog_increment: 100,
+            status: Status {
+                coordinates: Coordinates::Machine,
+                preset: Some("Roughing pass"),
+            },
+        };
+        let buffer = render(frame, Geometry::Lcd16x4);
+
+        assert_eq!(row(&buffer, 2).as_str(), "Machine         ");
+    }
+}
@@ -0,0 +1,160 @@
+/// Character missing in the HD44780 ROM, which has to be uploaded to CGRAM
+/// before it can be displayed.
+pub struct Glyph {
+    pub character: char,
+    pub pattern: [u8; 8],
+    /// ROM character displayed instead, when all CGRAM slots are taken.
+    pub fallback: u8,
+}
+
+pub fn find(character: char) -> Option<&'static Glyph> {
+    GLYPHS.iter().find(|glyph| glyph.character == character)
+}
+
+/// Returns code of a character, if it's in the ROM (A00 variant), but doesn't
+/// have the same code as in ASCII.
+pub fn rom_code(character: char) -> Option<u8> {
+    match character {
+        '→' => Some(0x7e),
+        '←' => Some(0x7f),
+        'ä' => Some(0xe1),
+        'ß' => Some(0xe2),
+        'ö' => Some(0xef),
+        'ü' => Some(0xf5),
+        '°' => Some(0xdf),
+        '█' => Some(0xff),
+        _ => None,
+    }
+}
+
+static GLYPHS: [Glyph; 18] = [
+    Glyph {
+        character: 'ą',
+        pattern: [
+            0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111, 0b00010,
+        ],
+        fallback: b'a',
+    },
+    Glyph {
+        character: 'ć',
+        pattern: [
+            0b00010, 0b00100, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110, 0b00000,
+        ],
+        fallback: b'c',
+    },
+    Glyph {
+        character: 'ę',
+        pattern: [
+            0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110, 0b00010,
+        ],
+        fallback: b'e',
+    },
+    Glyph {
+        character: 'ł',
+        pattern: [
+            0b01100, 0b00100, 0b00110, 0b01100, 0b00100, 0b00100, 0b01110, 0b00000,
+        ],
+        fallback: b'l',
+    },
+    Glyph {
+        character: 'ń',
+        pattern: [
+            0b00010, 0b00100, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001, 0b00000,
+        ],
+        fallback: b'n',
+    },
+    Glyph {
+        character: 'ó',
+        pattern: [
+            0b00010, 0b00100, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110, 0b00000,
+        ],
+        fallback: b'o',
+    },
+    Glyph {
+        character: 'ś',
+        pattern: [
+            0b00010, 0b00100, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110, 0b00000,
+        ],
+        fallback: b's',
+    },
+    Glyph {
+        character: 'ź',
+        pattern: [
+            0b00010, 0b00100, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000,
+        ],
+        fallback: b'z',
+    },
+    Glyph {
+        character: 'ż',
+        pattern: [
+            0b00100, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111, 0b00000,
+        ],
+        fallback: b'z',
+    },
+    Glyph {
+        character: '↑',
+        pattern: [
+            0b00100, 0b01110, 0b10101, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000,
+        ],
+        fallback: b'^',
+    },
+    Glyph {
+        character: '↓',
+        pattern: [
+            0b00100, 0b00100, 0b00100, 0b00100, 0b10101, 0b01110, 0b00100, 0b00000,
+        ],
+        fallback: b'v',
+    },
+    // Partially filled blocks, used to draw progress bars. Display cell is 5
+    // pixels wide, so each of them fills one column more.
+    Glyph {
+        character: '▏',
+        pattern: [
+            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000,
+        ],
+        fallback: b' ',
+    },
+    Glyph {
+        character: '▎',
+        pattern: [
+            0b11000, 0b11000, 0b11000, 0b11000, 0b11000, 0b11000, 0b11000, 0b11000,
+        ],
+        fallback: b' ',
+    },
+    Glyph {
+        character: '▍',
+        pattern: [
+            0b11100, 0b11100, 0b11100, 0b11100, 0b11100, 0b11100, 0b11100, 0b11100,
+        ],
+        fallback: 0xff,
+    },
+    Glyph {
+        character: '▋',
+        pattern: [
+            0b11110, 0b11110, 0b11110, 0b11110, 0b11110, 0b11110, 0b11110, 0b11110,
+        ],
+        fallback: 0xff,
+    },
+    // Segments of big digits, spanning two rows.
+    Glyph {
+        character: '▀',
+        pattern: [
+            0b11111, 0b11111, 0b11111, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000,
+        ],
+        fallback: b'"',
+    },
+    Glyph {
+        character: '▄',
+        pattern: [
+            0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111, 0b11111, 0b11111,
+        ],
+        fallback: b'_',
+    },
+    Glyph {
+        character: '≡',
+        pattern: [
+            0b11111, 0b11111, 0b11111, 0b00000, 0b00000, 0b11111, 0b11111, 0b11111,
+        ],
+        fallback: b'=',
+    },
+];
@@ -0,0 +1,290 @@
+use crate::display::Error;
+use embedded_hal::{
+    blocking::{
+        delay::{DelayMs, DelayUs},
+        i2c::Write,
+    },
+    digital::v2::OutputPin,
+};
+
+const CLEAR_DISPLAY: u8 = 0b0000_0001;
+const ENTRY_MODE_SET: u8 = 0b0000_0110;
+const DISPLAY_CONTROL: u8 = 0b0000_1000;
+const FUNCTION_SET: u8 = 0b0010_1000;
+const SET_CGRAM_ADDRESS: u8 = 0b0100_0000;
+const SET_DDRAM_ADDRESS: u8 = 0b1000_0000;
+
+/// Minimal HD44780 driver. Unlike `hd44780-driver` crate it gives access to
+/// the character generator RAM, so custom characters can be uploaded.
+pub struct HD44780<B: DataBus> {
+    bus: B,
+}
+
+impl<B: DataBus> HD44780<B> {
+    pub fn new(bus: B, delay: &mut (impl DelayMs<u8> + DelayUs<u16>)) -> Result<Self, Error> {
+        let mut hd44780 = Self { bus };
+
+        // Initialization by instruction, as described in the datasheet. It
+        // works no matter in which mode the controller was left before reset.
+        delay.delay_ms(50);
+        hd44780.bus.write_nibble(0b0011, false, delay)?;
+        delay.delay_ms(5);
+        hd44780.bus.write_nibble(0b0011, false, delay)?;
+        delay.delay_us(150);
+        hd44780.bus.write_nibble(0b0011, false, delay)?;
+        delay.delay_us(150);
+        hd44780.bus.write_nibble(0b0010, false, delay)?;
+        delay.delay_us(150);
+
+        hd44780.write_command(FUNCTION_SET, delay)?;
+        hd44780.set_display_mode(DisplayMode::default(), delay)?;
+        hd44780.clear(delay)?;
+        hd44780.write_command(ENTRY_MODE_SET, delay)?;
+
+        Ok(hd44780)
+    }
+
+    pub fn clear(&mut self, delay: &mut (impl DelayMs<u8> + DelayUs<u16>)) -> Result<(), Error> {
+        self.write_command(CLEAR_DISPLAY, delay)?;
+        delay.delay_ms(2);
+        Ok(())
+    }
+
+    pub fn set_display_mode(
+        &mut self,
+        mode: DisplayMode,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        let mut command = DISPLAY_CONTROL;
+        if mode.display {
+            command |= 0b100;
+        }
+        if mode.cursor {
+            command |= 0b010;
+        }
+        if mode.cursor_blink {
+            command |= 0b001;
+        }
+
+        self.write_command(command, delay)
+    }
+
+    pub fn set_cursor_pos(
+        &mut self,
+        position: u8,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.write_command(SET_DDRAM_ADDRESS | (position & 0b0111_1111), delay)
+    }
+
+    /// Uploads 5x8 pattern of a custom character to one of 8 CGRAM slots.
+    /// Afterwards the controller writes data to CGRAM, so cursor position has
+    /// to be set before writing any text.
+    pub fn set_custom_char(
+        &mut self,
+        slot: u8,
+        pattern: &[u8; 8],
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.write_command(SET_CGRAM_ADDRESS | ((slot & 0b111) << 3), delay)?;
+        for row in pattern {
+            self.write_byte(*row, delay)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_byte(
+        &mut self,
+        byte: u8,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.bus.write(byte, true, delay)
+    }
+
+    pub fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        for byte in bytes {
+            self.write_byte(*byte, delay)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_str(
+        &mut self,
+        string: &str,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.write_bytes(string.as_bytes(), delay)
+    }
+
+    fn write_command(
+        &mut self,
+        command: u8,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.bus.write(command, false, delay)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DisplayMode {
+    pub display: bool,
+    pub cursor: bool,
+    pub cursor_blink: bool,
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        Self {
+            display: true,
+            cursor: false,
+            cursor_blink: false,
+        }
+    }
+}
+
+/// Interface over which the controller is connected. Only 4-bit interfaces
+/// are supported.
+pub trait DataBus {
+    fn write_nibble(
+        &mut self,
+        nibble: u8,
+        data: bool,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error>;
+
+    fn write(
+        &mut self,
+        byte: u8,
+        data: bool,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        self.write_nibble(byte >> 4, data, delay)?;
+        self.write_nibble(byte & 0b1111, data, delay)?;
+
+        // Most of the instructions take 37us to execute.
+        delay.delay_us(50);
+        Ok(())
+    }
+}
+
+pub struct FourBitBus<RS, EN, D4, D5, D6, D7>
+where
+    RS: OutputPin,
+    EN: OutputPin,
+    D4: OutputPin,
+    D5: OutputPin,
+    D6: OutputPin,
+    D7: OutputPin,
+{
+    rs: RS,
+    en: EN,
+    d4: D4,
+    d5: D5,
+    d6: D6,
+    d7: D7,
+}
+
+impl<RS, EN, D4, D5, D6, D7> FourBitBus<RS, EN, D4, D5, D6, D7>
+where
+    RS: OutputPin,
+    EN: OutputPin,
+    D4: OutputPin,
+    D5: OutputPin,
+    D6: OutputPin,
+    D7: OutputPin,
+{
+    pub fn new(rs: RS, en: EN, d4: D4, d5: D5, d6: D6, d7: D7) -> Self {
+        Self {
+            rs,
+            en,
+            d4,
+            d5,
+            d6,
+            d7,
+        }
+    }
+}
+
+impl<RS, EN, D4, D5, D6, D7> DataBus for FourBitBus<RS, EN, D4, D5, D6, D7>
+where
+    RS: OutputPin,
+    EN: OutputPin,
+    D4: OutputPin,
+    D5: OutputPin,
+    D6: OutputPin,
+    D7: OutputPin,
+{
+    fn write_nibble(
+        &mut self,
+        nibble: u8,
+        data: bool,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        set_pin(&mut self.rs, data)?;
+        set_pin(&mut self.d4, nibble & 0b0001 != 0)?;
+        set_pin(&mut self.d5, nibble & 0b0010 != 0)?;
+        set_pin(&mut self.d6, nibble & 0b0100 != 0)?;
+        set_pin(&mut self.d7, nibble & 0b1000 != 0)?;
+
+        set_pin(&mut self.en, true)?;
+        delay.delay_us(1);
+        set_pin(&mut self.en, false)?;
+        delay.delay_us(1);
+
+        Ok(())
+    }
+}
+
+/// PCF8574 based I2C backpack. Its outputs are wired as follows: P0 - RS,
+/// P1 - RW, P2 - EN, P3 - backlight, P4-P7 - D4-D7.
+pub struct I2CBus<I2C: Write> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: Write> I2CBus<I2C> {
+    const RS: u8 = 0b0000_0001;
+    const EN: u8 = 0b0000_0100;
+    const BACKLIGHT: u8 = 0b0000_1000;
+
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl<I2C: Write> DataBus for I2CBus<I2C> {
+    fn write_nibble(
+        &mut self,
+        nibble: u8,
+        data: bool,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        let mut byte = (nibble << 4) | Self::BACKLIGHT;
+        if data {
+            byte |= Self::RS;
+        }
+
+        self.i2c
+            .write(self.address, &[byte | Self::EN])
+            .map_err(|_| Error)?;
+        delay.delay_us(1);
+        self.i2c.write(self.address, &[byte]).map_err(|_| Error)?;
+        delay.delay_us(1);
+
+        Ok(())
+    }
+}
+
+fn set_pin(pin: &mut impl OutputPin, high: bool) -> Result<(), Error> {
+    if high {
+        pin.set_high().map_err(|_| Error)
+    } else {
+        pin.set_low().map_err(|_| Error)
+    }
+}
@@ -1,304 +1,2151 @@
 #![no_std]
 
+pub mod backlight;
+pub mod display;
+pub mod fault;
+pub mod foot_pedal;
+pub mod frame;
+pub mod glyph;
+pub mod hd44780;
+pub mod locale;
+pub mod menu;
+// Hardware doubles shared by the unit tests.
+#[cfg(test)]
+mod mock;
+#[cfg(feature = "oled")]
+pub mod oled;
+pub mod pendant;
+pub mod preset;
+pub mod render;
 pub mod rotary_encoder;
 pub mod screen;
+pub mod settings;
+pub mod state;
 pub mod stepper_motor;
+pub mod storage;
+pub mod unit;
 
+use backlight::Backlight;
+use display::DisplayBackend;
 use embedded_hal::{
     blocking::delay::{DelayMs, DelayUs},
     digital::v2::{InputPin, OutputPin},
 };
+use embedded_storage::nor_flash::NorFlash;
+use fault::Fault;
+use foot_pedal::{Direction, FootPedal};
+use frame::{Diagnostics, Frame, Status};
+use menu::{Coordinates, Menu};
+use pendant::Pendant;
+use preset::{Preset, PRESET_COUNT};
+use render::Render;
 use rotary_encoder::{RotaryEncoder, Rotation};
 use rtcc::Rtcc;
-use screen::{Frame, Screen, ScreenUpdateError};
+use screen::{Screen, ScreenUpdateError};
+use settings::Settings;
+use state::{Action, Event, HomingPhase, Limit, MillState, ProbingPhase};
 use stepper_motor::StepperMotor;
+use storage::{Record, Storage};
 
-pub struct Mill<SIA, SIB, HOM, LIM, STP, DIR, MEN, M1, M2, DUR, RS, SEN, D4, D5, D6, D7>
-where
+// Jog increments of the encoder in hundredths of a millimetre, cycled by
+// clicking its button. They stay metric when heights are shown in inches.
+const JOG_INCREMENTS: [u32; 4] = [1, 10, 100, 1000];
+
+pub struct Mill<
+    SIA,
+    SIB,
+    FIA,
+    FIB,
+    HOM,
+    BTN,
+    LLM,
+    ULM,
+    PRB,
+    PUP,
+    PDN,
+    MPA,
+    MPB,
+    X10,
+    X100,
+    STP,
+    DIR,
+    MEN,
+    M1,
+    M2,
+    DUR,
+    DSP,
+    BKL,
+    FLS,
+> where
     SIA: InputPin,
     SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
     HOM: InputPin,
-    LIM: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     MEN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
-    DUR: Copy,
-    RS: OutputPin,
-    SEN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
+    DUR: Copy + From<u8>,
+    DSP: DisplayBackend,
+    BKL: Backlight,
+    FLS: NorFlash,
 {
     pub encoder: RotaryEncoder<SIA, SIB>,
+    pub fine_encoder: RotaryEncoder<FIA, FIB>,
     motor: StepperMotor<STP, DIR, MEN, M1, M2, DUR>,
-    screen: Screen<RS, SEN, D4, D5, D6, D7>,
-    pub limit_switch: LIM,
+    screen: Screen<DSP, BKL>,
+    pub lower_limit_switch: LLM,
+    pub upper_limit_switch: ULM,
+    probe: PRB,
     pub home_switch: HOM,
+    pub button: BTN,
+    foot_pedal: FootPedal<PUP, PDN>,
+    pub pendant: Pendant<MPA, MPB, X10, X100>,
+    storage: Storage<FLS>,
 
+    state: MillState,
     target_height: u32,
-    current_height: Option<u32>,
+    current_height: u32,
+    // Height at which current motion started, used to draw its progress.
+    move_start_height: Option<u32>,
+    // Height saved in the flash while the lift stands still, offered to be
+    // resumed after the power is switched off.
+    saved_height: Option<u32>,
+    // Whether resuming is chosen instead of homing, while it's offered.
+    resume: bool,
+    // Work zero in motor steps, and whether heights are shown relative to it.
+    // Soft limits stay in machine coordinates.
+    work_zero: u32,
+    work_coordinates: bool,
+    // Distance the encoder moves the target per detent, in hundredths of a
+    // millimetre.
+    jog_increment: u32,
+    // Time the button was pressed at, in milliseconds since boot, until it's
+    // released or held long enough to open the menu.
+    button_pressed_at: Option<u32>,
+    ticks_since_refresh: u32,
+    // Open settings menu. While it's open, the encoder controls the menu
+    // instead of the lift.
+    menu: Option<Menu>,
+    // Last fault, kept after it's acknowledged for the diagnostics screen.
+    last_fault: Option<Fault>,
+    diagnostics: bool,
+    // Seconds since the last input or motion, counted by watching RTC
+    // seconds change.
+    idle_seconds: u32,
+    last_rtc_seconds: Option<u8>,
+    // Distance travelled in the current homing or probing phase, in motor
+    // steps.
+    travel: u32,
+    // Milliseconds since boot at the last tick, and when homing started. RTC
+    // seconds are reset on every input, so they can't time homing.
+    milliseconds: u32,
+    homing_started_at: u32,
 
+    settings: Settings,
+    presets: [Preset; PRESET_COUNT],
     motor_steps_per_tick: u32,
-    motor_steps_per_mm: u32,
-    max_height: u32,
+    screen_refresh_ticks: u32,
+    big_digits: bool,
+    menu_hold_ms: u32,
+    homing: HomingConfig,
+    probing: ProbingConfig,
 }
 
-impl<SIA, SIB, HOM, LIM, STP, DIR, MEN, M1, M2, DUR, RS, SEN, D4, D5, D6, D7>
-    Mill<SIA, SIB, HOM, LIM, STP, DIR, MEN, M1, M2, DUR, RS, SEN, D4, D5, D6, D7>
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        HOM,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        MEN,
+        M1,
+        M2,
+        DUR,
+        DSP,
+        BKL,
+        FLS,
+    >
+    Mill<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        HOM,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        MEN,
+        M1,
+        M2,
+        DUR,
+        DSP,
+        BKL,
+        FLS,
+    >
 where
     SIA: InputPin,
     SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
     HOM: InputPin,
-    LIM: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     MEN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
-    DUR: Copy,
-    RS: OutputPin,
-    SEN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
+    DUR: Copy + From<u8>,
+    DSP: DisplayBackend,
+    BKL: Backlight,
+    FLS: NorFlash,
 {
+    /// Errors at boot put the mill in the fault state, the same as errors
+    /// later on.
+    // Pins of the config are spelled out, so the ones of the mill are
+    // inferred from it.
+    #[allow(clippy::type_complexity)]
     pub fn new(
-        config: MillConfig<SIA, SIB, HOM, LIM, STP, DIR, MEN, M1, M2, DUR, RS, SEN, D4, D5, D6, D7>,
-        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
-    ) -> Result<Self, Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
+        config: MillConfig<
+            SIA,
+            SIB,
+            FIA,
+            FIB,
+            HOM,
+            BTN,
+            LLM,
+            ULM,
+            PRB,
+            PUP,
+            PDN,
+            MPA,
+            MPB,
+            X10,
+            X100,
+            STP,
+            DIR,
+            MEN,
+            M1,
+            M2,
+            DUR,
+            DSP,
+            BKL,
+            FLS,
+        >,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Self {
         let MillConfig {
             encoder,
+            fine_encoder,
             motor,
             screen,
-            limit_switch,
+            lower_limit_switch,
+            upper_limit_switch,
+            probe,
             home_switch,
+            button,
+            foot_pedal,
+            pendant,
+            storage,
 
-            max_height,
-            motor_steps_per_mm,
+            settings,
+            presets,
+            saved_height,
             motor_steps_per_tick,
+            screen_refresh_ticks,
+            big_digits,
+            menu_hold_ms,
+            homing,
+            probing,
             ..
         } = config;
 
         let mut mill = Self {
             encoder,
+            fine_encoder,
             motor,
             screen,
-            limit_switch,
+            lower_limit_switch,
+            upper_limit_switch,
+            probe,
             home_switch,
+            button,
+            foot_pedal,
+            pendant,
+            storage,
 
-            current_height: None,
+            state: MillState::Uncalibrated,
+            current_height: 0,
             target_height: 0,
+            move_start_height: None,
+            saved_height: None,
+            resume: true,
+            work_zero: 0,
+            work_coordinates: false,
+            jog_increment: 100,
+            button_pressed_at: None,
+            ticks_since_refresh: 0,
+            menu: None,
+            last_fault: None,
+            diagnostics: false,
+            idle_seconds: 0,
+            last_rtc_seconds: None,
+            travel: 0,
+            milliseconds: 0,
+            homing_started_at: 0,
 
-            max_height,
-            motor_steps_per_mm,
+            settings,
+            presets,
             motor_steps_per_tick,
+            screen_refresh_ticks,
+            big_digits,
+            menu_hold_ms,
+            homing,
+            probing,
         };
 
-        mill.screen.update(Frame::Calibrating, delay)?;
+        mill.apply_settings(settings);
+        if let Some(height) = saved_height.filter(|height| *height <= mill.max_height()) {
+            mill.saved_height = Some(height);
+            mill.state = MillState::Resume;
+        }
+        if let Err(err) = mill.update_screen(delay) {
+            mill.enter_fault(err, delay);
+        }
 
-        Ok(mill)
+        mill
     }
 
+    /// Moves the lift and keeps the screen up to date. Has to be called in
+    /// the main loop, with milliseconds since boot.
     pub fn tick(
         &mut self,
         delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
         rtc: &mut impl Rtcc,
-    ) -> Result<(), Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
-        if let Some(current_height) = self.current_height {
-            if rtc
-                .get_seconds()
-                .map(|seconds| seconds < 1)
-                .unwrap_or(false)
-            {
-                return Ok(());
-            }
+        milliseconds: u32,
+    ) -> Result<(), MillError<Self>> {
+        self.milliseconds = milliseconds;
 
-            if current_height > self.target_height {
-                self.motor
-                    .rotate_counter_clockwise(self.motor_steps_per_tick, delay)?;
-                self.current_height
-                    .replace(current_height - self.motor_steps_per_tick);
-            } else if current_height < self.target_height {
-                self.motor
-                    .rotate_clockwise(self.motor_steps_per_tick, delay)?;
-                self.current_height
-                    .replace(current_height + self.motor_steps_per_tick);
+        // RTC seconds are reset on every input, so only their change
+        // matters.
+        let rtc_seconds = rtc.get_seconds().ok();
+        if rtc_seconds != self.last_rtc_seconds {
+            self.last_rtc_seconds = rtc_seconds;
+            self.idle_seconds += 1;
+            self.screen.set_idle_time(self.idle_seconds)?;
+        }
+
+        if let Some(pressed_at) = self.button_pressed_at {
+            if milliseconds.wrapping_sub(pressed_at) >= self.menu_hold_ms {
+                self.button_pressed_at = None;
+                self.open_menu();
+                self.update_screen(delay)?;
             }
-        } else {
-            self.motor
-                .rotate_counter_clockwise(self.motor_steps_per_tick, delay)?;
-            if self
-                .limit_switch
-                .is_low()
-                .map_err(|err| Error::LimitSwitch(err))?
-            {
-                self.current_height.replace(0);
+        }
+
+        // Diagnostics show live state of the inputs, so they are refreshed
+        // all the time.
+        if self.diagnostics {
+            self.ticks_since_refresh += 1;
+            if self.ticks_since_refresh >= self.screen_refresh_ticks {
+                self.ticks_since_refresh = 0;
                 self.update_screen(delay)?;
             }
         }
 
-        Ok(())
+        let event = Event::Tick {
+            pedal: self.foot_pedal.direction()?,
+            settled: rtc
+                .get_seconds()
+                .map(|seconds| seconds >= 1)
+                .unwrap_or(true),
+            at_target: self.current_height == self.target_height,
+        };
+        self.handle_event(event, delay)
     }
 
     pub fn handle_sia_interrupt(
         &mut self,
-        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+        rtc: &mut impl Rtcc,
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        if self.fault().is_some() {
+            return Ok(());
+        }
+
+        let rotation = self.encoder.update()?;
+        if let Some(menu) = self.menu.as_mut() {
+            menu.rotate(rotation);
+        } else if self.state == MillState::Resume {
+            if rotation != Rotation::None {
+                self.resume = !self.resume;
+            }
+        } else {
+            self.jog_target(rotation, self.jog_increment);
+            rtc.set_seconds(0);
+            self.handle_event(Event::TargetChanged, delay)?;
+        }
+        self.update_screen(delay)
+    }
+
+    pub fn handle_fine_encoder_interrupt(
+        &mut self,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
         rtc: &mut impl Rtcc,
-    ) -> Result<(), Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
-        match self.encoder.update()? {
-            Rotation::Clockwise => {
-                self.target_height += self.motor_steps_per_mm;
-                if self.target_height > self.max_height {
-                    self.target_height = self.max_height;
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        if self.fault().is_some() {
+            return Ok(());
+        }
+
+        let rotation = self
+            .fine_encoder
+            .update()
+            .map_err(|err| Error::FineEncoder(err))?;
+        if self.menu.is_some() {
+            return Ok(());
+        }
+        // One detent of the fine knob is a hundredth of a millimetre.
+        self.jog_target(rotation, 1);
+        rtc.set_seconds(0);
+        self.handle_event(Event::TargetChanged, delay)?;
+        self.update_screen(delay)
+    }
+
+    pub fn handle_pendant_interrupt(
+        &mut self,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+        rtc: &mut impl Rtcc,
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        if self.fault().is_some() {
+            return Ok(());
+        }
+
+        // At x1 one pendant pulse is a hundredth of a milimeter.
+        let hundredths = self.pendant.multiplier()?;
+        let rotation = self.pendant.update()?;
+        if self.menu.is_some() {
+            return Ok(());
+        }
+        self.jog_target(rotation, hundredths);
+        rtc.set_seconds(0);
+        self.handle_event(Event::TargetChanged, delay)?;
+        self.update_screen(delay)
+    }
+
+    /// Acknowledges the fault, or passes the press to the menu when it's
+    /// open. Recalling a preset starts moving to it right away. Otherwise a
+    /// click cycles the jog increment, and holding the button opens the
+    /// settings menu, which the tick does once it's held long enough. Pressed
+    /// with both pedals held, it opens the diagnostics screen instead, and
+    /// any press closes it. Has to be called on both edges of the button,
+    /// with milliseconds since boot.
+    pub fn handle_button_interrupt(
+        &mut self,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+        milliseconds: u32,
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        if !self.button.is_high().map_err(|err| Error::Button(err))? {
+            // Release ends a click, unless the press was taken as a hold.
+            if self.button_pressed_at.take().is_some() {
+                let position = JOG_INCREMENTS
+                    .iter()
+                    .position(|increment| *increment == self.jog_increment)
+                    .unwrap_or(0);
+                self.jog_increment = JOG_INCREMENTS[(position + 1) % JOG_INCREMENTS.len()];
+                return self.update_screen(delay);
+            }
+            return Ok(());
+        }
+
+        if self.diagnostics || self.foot_pedal.both_pressed()? {
+            self.diagnostics = !self.diagnostics;
+            return self.update_screen(delay);
+        }
+
+        if self.fault().is_some() {
+            self.handle_event(Event::Acknowledge, delay)?;
+            return self.update_screen(delay);
+        }
+
+        if self.state == MillState::Resume {
+            let event = if self.resume {
+                Event::Resume
+            } else {
+                Event::Rehome
+            };
+            self.handle_event(event, delay)?;
+            return self.update_screen(delay);
+        }
+
+        let action = match self.menu.as_mut() {
+            None => {
+                self.button_pressed_at = Some(milliseconds);
+                return Ok(());
+            }
+            Some(menu) => menu.press(),
+        };
+
+        match action {
+            Some(menu::Action::Save(settings)) => {
+                self.apply_settings(settings);
+                self.menu = None;
+                self.save()?;
+            }
+            Some(menu::Action::Cancel) => self.menu = None,
+            Some(menu::Action::Recall(slot)) => {
+                self.menu = None;
+                if let (true, Some(height)) =
+                    (self.state.is_calibrated(), self.presets[slot].height)
+                {
+                    self.move_start_height = None;
+                    self.target_height = height.min(self.max_height());
+                    self.handle_event(Event::TargetChanged, delay)?;
+                }
+            }
+            Some(menu::Action::SavePreset(slot)) => {
+                self.menu = None;
+                if self.state.is_calibrated() {
+                    self.presets[slot].height = Some(self.target_height);
+                    self.save()?;
+                }
+            }
+            Some(menu::Action::SetZero) => {
+                self.menu = None;
+                if self.state.is_calibrated() {
+                    self.work_zero = self.current_height.min(self.max_height());
+                    self.work_coordinates = true;
                 }
             }
-            Rotation::CounterClockwise => {
-                self.target_height = self
-                    .target_height
-                    .checked_sub(self.motor_steps_per_mm)
-                    .unwrap_or(0)
+            Some(menu::Action::Probe) => {
+                self.menu = None;
+                self.handle_event(Event::Probe, delay)?;
             }
-            _ => {}
+            Some(menu::Action::SwitchCoordinates) => {
+                self.menu = None;
+                self.work_coordinates = !self.work_coordinates;
+            }
+            None => {}
         }
-        rtc.set_seconds(0);
+
         self.update_screen(delay)
     }
 
     pub fn handle_home_switch_interrupt(
         &mut self,
-        delay: &mut (impl DelayUs<u16> + DelayMs<u8>),
-    ) -> Result<(), Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
-        self.current_height = None;
-        self.update_screen(delay)
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        // The lift is stopped first, so a screen error can't keep it moving.
+        self.handle_event(Event::HomeSwitch, delay)?;
+        self.wake()
     }
 
     pub fn handle_limit_switch_interrupt(
         &mut self,
-        delay: &mut (impl DelayUs<u16> + DelayMs<u8> + DelayUs<DUR> + DelayMs<DUR>),
-    ) -> Result<(), Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
-        self.current_height = Some(self.motor_steps_per_mm);
-        self.target_height = self.motor_steps_per_mm;
+        limit: Limit,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        // The lift is stopped first, so a screen error can't keep it moving.
+        self.handle_event(Event::LimitSwitch(limit), delay)?;
+        self.wake()?;
+
+        if self.homing.rehome_after_limit && self.fault() == Some(limit.fault()) {
+            self.handle_event(Event::Acknowledge, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the backlight back to full brightness after an input or
+    /// motion.
+    fn wake(&mut self) -> Result<(), MillError<Self>> {
+        self.idle_seconds = 0;
+        self.screen.set_idle_time(0)?;
+        Ok(())
+    }
+
+    /// Draws a frame of the application instead of the built-in one. It's
+    /// replaced on the next input or motion, which redraws the screen.
+    pub fn show(
+        &mut self,
+        frame: impl Render,
+        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        self.screen.update(frame, delay)?;
+        Ok(())
+    }
+
+    pub fn state(&self) -> MillState {
+        self.state
+    }
+
+    pub fn fault(&self) -> Option<Fault> {
+        match self.state {
+            MillState::Fault(fault) => Some(fault),
+            _ => None,
+        }
+    }
+
+    /// Stops the lift after an error and shows it on the screen. Position of
+    /// the lift is forgotten, so it's homed again after the fault is
+    /// acknowledged.
+    pub fn enter_fault(
+        &mut self,
+        error: MillError<Self>,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) {
+        self.handle_event(Event::Error(error.fault()), delay).ok();
+    }
+
+    fn handle_event(
+        &mut self,
+        event: Event,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        let (state, action) = state::transition(self.state, event);
+        // Checked before the state changes, so a probe which can't start
+        // keeps the saved height and doesn't touch the flash.
+        if action == Action::StartProbing && self.is_probe_touching()? {
+            return self.handle_event(Event::Error(Fault::ProbeTouching), delay);
+        }
+        self.state = state;
+
+        // Saved position can't be trusted anymore once the lift moves. Only
+        // the record is marked, as a new one is saved when the lift stops.
+        if self.state.is_moving() && self.saved_height.is_some() {
+            self.saved_height = None;
+            self.storage
+                .mark_moving()
+                .map_err(|err| Error::Storage(err))?;
+        }
+
+        match action {
+            Action::None => Ok(()),
+            Action::StartHoming => {
+                self.travel = 0;
+                self.homing_started_at = self.milliseconds;
+                Ok(())
+            }
+            Action::Home(phase) => self.home(phase, delay),
+            Action::ResetTravel => {
+                self.travel = 0;
+                Ok(())
+            }
+            Action::SetHome => {
+                self.current_height = match self.homing.end {
+                    Limit::Lower => 0,
+                    Limit::Upper => self.max_height(),
+                };
+                self.target_height = self.current_height;
+                self.save_height()?;
+                self.update_screen(delay)
+            }
+            Action::Resume => {
+                if let Some(height) = self.saved_height {
+                    self.current_height = height;
+                    self.target_height = height;
+                }
+                self.update_screen(delay)
+            }
+            Action::StartProbing => {
+                self.travel = 0;
+                self.move_start_height = None;
+                self.update_screen(delay)
+            }
+            Action::Probe(phase) => self.probe(phase, delay),
+            Action::SetWorkZero => {
+                let plate_thickness = self.steps(self.probing.plate_thickness);
+                self.work_zero = self
+                    .current_height
+                    .saturating_sub(plate_thickness)
+                    .min(self.max_height());
+                self.work_coordinates = true;
+                self.travel = 0;
+                Ok(())
+            }
+            Action::Step => self.step(delay),
+            Action::Jog(direction) => {
+                // Jogging with the pedal moves target together with the
+                // motor, so lift stops as soon as the pedal is released.
+                self.target_height = match direction {
+                    Direction::Up => {
+                        (self.current_height + self.motor_steps_per_tick).min(self.max_height())
+                    }
+                    Direction::Down => self
+                        .current_height
+                        .saturating_sub(self.motor_steps_per_tick),
+                };
+                if self.current_height != self.target_height {
+                    self.step(delay)?;
+                }
+                Ok(())
+            }
+            Action::Stop => {
+                self.move_start_height = None;
+                self.save_height()?;
+                self.update_screen(delay)
+            }
+            Action::Forget => {
+                self.move_start_height = None;
+                self.update_screen(delay)
+            }
+            Action::Halt => {
+                // The lift stays at home after it's homed again. Errors of
+                // the motor and the screen are ignored here, as there is
+                // nothing more to do about them.
+                self.last_fault = self.fault();
+                self.motor.disable().ok();
+                self.target_height = 0;
+                self.move_start_height = None;
+                self.menu = None;
+                self.wake().ok();
+                self.update_screen(delay).ok();
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves the lift a bit in the homing phase. Approaches fault when the
+    /// switch isn't found in time or within the maximum travel.
+    fn home(
+        &mut self,
+        phase: HomingPhase,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        let homing_time = self.milliseconds.wrapping_sub(self.homing_started_at);
+        if homing_time > self.homing.timeout.saturating_mul(1000) {
+            return self.handle_event(Event::Error(Fault::HomingTimeout), delay);
+        }
+
+        match phase {
+            HomingPhase::FastApproach | HomingPhase::SlowApproach => {
+                if self.travel >= self.steps(self.homing.max_travel) {
+                    return self.handle_event(Event::Error(Fault::HomingTravel), delay);
+                }
+
+                if phase == HomingPhase::FastApproach {
+                    self.rotate_towards(self.homing.end, self.motor_steps_per_tick, delay)?;
+                    self.travel += self.motor_steps_per_tick;
+                } else {
+                    let signal_delay = self.motor.signal_delay();
+                    self.motor.set_signal_delay(
+                        signal_delay.with_value(self.homing.slow_signal_delay.into()),
+                    );
+                    let result = self.rotate_towards(self.homing.end, 1, delay);
+                    self.motor.set_signal_delay(signal_delay);
+                    result?;
+                    self.travel += 1;
+                }
+
+                if self.is_limit_switch_triggered(self.homing.end)? {
+                    self.handle_event(Event::SwitchReached, delay)?;
+                }
+            }
+            HomingPhase::BackOff | HomingPhase::Offset => {
+                let distance = if phase == HomingPhase::BackOff {
+                    self.steps(self.homing.back_off)
+                } else {
+                    self.steps(self.homing.offset)
+                };
+
+                if self.travel >= distance {
+                    return self.handle_event(Event::DistanceTravelled, delay);
+                }
+
+                self.rotate_away_from(self.homing.end, self.motor_steps_per_tick, delay)?;
+                self.travel += self.motor_steps_per_tick;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lift moves up when the motor rotates clockwise.
+    fn rotate_towards(
+        &mut self,
+        limit: Limit,
+        steps: u32,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        match limit {
+            Limit::Lower => self.motor.rotate_counter_clockwise(steps, delay)?,
+            Limit::Upper => self.motor.rotate_clockwise(steps, delay)?,
+        }
+        Ok(())
+    }
+
+    fn rotate_away_from(
+        &mut self,
+        limit: Limit,
+        steps: u32,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        match limit {
+            Limit::Lower => self.rotate_towards(Limit::Upper, steps, delay),
+            Limit::Upper => self.rotate_towards(Limit::Lower, steps, delay),
+        }
+    }
+
+    /// Moves the lift a bit in the probing phase. Approach faults when the
+    /// bit doesn't touch the plate within the maximum distance, or before it
+    /// reaches the end of the travel. Target follows the lift, so it stays
+    /// where probing ended.
+    fn probe(
+        &mut self,
+        phase: ProbingPhase,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+
+        let towards = self.probing.direction;
+        let away = match towards {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        };
+
+        match phase {
+            ProbingPhase::Approach => {
+                if self.travel >= self.steps(self.probing.max_distance)
+                    || self.travel_left(towards) == 0
+                {
+                    return self.handle_event(Event::Error(Fault::ProbeDistance), delay);
+                }
+
+                let signal_delay = self.motor.signal_delay();
+                self.motor
+                    .set_signal_delay(signal_delay.with_value(self.probing.signal_delay.into()));
+                let result = self.move_by(towards, 1, delay);
+                self.motor.set_signal_delay(signal_delay);
+                result?;
+                self.travel += 1;
+
+                if self.is_probe_touching()? {
+                    self.handle_event(Event::SwitchReached, delay)?;
+                }
+            }
+            ProbingPhase::Retract => {
+                let steps = self
+                    .motor_steps_per_tick
+                    .min(self.steps(self.probing.retract).saturating_sub(self.travel))
+                    .min(self.travel_left(away));
+                if steps == 0 {
+                    return self.handle_event(Event::DistanceTravelled, delay);
+                }
+
+                self.move_by(away, steps, delay)?;
+                self.travel += steps;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the lift by the number of steps, keeping track of its height.
+    fn move_by(
+        &mut self,
+        direction: Direction,
+        steps: u32,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        match direction {
+            Direction::Up => {
+                self.current_height += steps;
+                self.rotate_towards(Limit::Upper, steps, delay)?;
+            }
+            Direction::Down => {
+                self.current_height -= steps;
+                self.rotate_towards(Limit::Lower, steps, delay)?;
+            }
+        }
+        self.target_height = self.current_height;
+        Ok(())
+    }
+
+    /// Steps left to the end of the travel in the direction.
+    fn travel_left(&self, direction: Direction) -> u32 {
+        match direction {
+            Direction::Up => self.max_height().saturating_sub(self.current_height),
+            Direction::Down => self.current_height,
+        }
+    }
+
+    fn is_probe_touching(&self) -> Result<bool, MillError<Self>> {
+        self.probe.is_low().map_err(|err| Error::Probe(err))
+    }
+
+    fn is_limit_switch_triggered(&self, limit: Limit) -> Result<bool, MillError<Self>> {
+        match limit {
+            Limit::Lower => self
+                .lower_limit_switch
+                .is_low()
+                .map_err(|err| Error::LowerLimitSwitch(err)),
+            Limit::Upper => self
+                .upper_limit_switch
+                .is_low()
+                .map_err(|err| Error::UpperLimitSwitch(err)),
+        }
+    }
+
+    /// Moves the lift a bit towards the target.
+    fn step(
+        &mut self,
+        delay: &mut (impl DelayMs<DUR> + DelayUs<DUR> + DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), MillError<Self>> {
+        self.wake()?;
+        if self.move_start_height.is_none() {
+            self.move_start_height = Some(self.current_height);
+            self.ticks_since_refresh = self.screen_refresh_ticks;
+        }
+
+        if self.current_height > self.target_height {
+            self.motor
+                .rotate_counter_clockwise(self.motor_steps_per_tick, delay)?;
+            self.current_height -= self.motor_steps_per_tick;
+        } else {
+            self.motor
+                .rotate_clockwise(self.motor_steps_per_tick, delay)?;
+            self.current_height += self.motor_steps_per_tick;
+        }
+
+        // Updating the screen takes much longer than a single tick, so
+        // during motion it's refreshed only every few ticks.
+        self.ticks_since_refresh += 1;
+        if self.ticks_since_refresh >= self.screen_refresh_ticks {
+            self.ticks_since_refresh = 0;
+            self.update_screen(delay)?;
+        }
+
+        Ok(())
+    }
+
+    /// Heights are kept in motor steps, so they stay valid when the number of
+    /// steps per milimeter changes. Only the target is clamped to the new
+    /// maximum height. When the lift is homed at the upper end, its height
+    /// is counted from there, so heights and presets move with the maximum
+    /// height. Presets are saved by the caller.
+    fn apply_settings(&mut self, settings: Settings) {
+        let old_max_height = self.max_height();
+        self.settings = settings;
+        if self.homing.end == Limit::Upper {
+            let max_height = self.max_height();
+            let shift = |height: u32| (height + max_height).saturating_sub(old_max_height);
+            self.current_height = shift(self.current_height);
+            self.target_height = shift(self.target_height);
+            self.saved_height = self.saved_height.map(shift);
+            self.work_zero = shift(self.work_zero);
+            for preset in self.presets.iter_mut() {
+                preset.height = preset.height.map(|height| shift(height).min(max_height));
+            }
+        }
+
+        self.screen.set_language(settings.language);
+        self.screen.set_unit(settings.unit);
+        let signal_delay = self.motor.signal_delay();
         self.motor
-            .rotate_clockwise(self.motor_steps_per_mm, delay)?;
-        self.update_screen(delay)
+            .set_signal_delay(signal_delay.with_value(settings.signal_delay.into()));
+        self.target_height = self.target_height.min(self.max_height());
+        self.work_zero = self.work_zero.min(self.max_height());
+    }
+
+    fn max_height(&self) -> u32 {
+        self.settings.max_height * self.settings.motor_steps_per_mm
+    }
+
+    /// Converts distance in hundredths of a millimetre to motor steps with
+    /// the current settings.
+    fn steps(&self, hundredths: u32) -> u32 {
+        round_div(
+            hundredths as i64 * self.settings.motor_steps_per_mm as i64,
+            100,
+        ) as u32
+    }
+
+    /// Saves the height at which the lift stopped.
+    fn save_height(&mut self) -> Result<(), MillError<Self>> {
+        self.saved_height = Some(self.current_height);
+        self.save()
+    }
+
+    /// Writes settings, presets and the saved height to the flash.
+    fn save(&mut self) -> Result<(), MillError<Self>> {
+        let mut presets = [None; PRESET_COUNT];
+        for (height, preset) in presets.iter_mut().zip(self.presets.iter()) {
+            *height = preset.height;
+        }
+
+        let record = Record {
+            settings: self.settings,
+            presets,
+            position: self.saved_height,
+        };
+        self.storage
+            .save(&record)
+            .map_err(|err| Error::Storage(err))
+    }
+
+    fn open_menu(&mut self) {
+        let work_zero = if self.work_coordinates {
+            Some(self.work_zero)
+        } else {
+            None
+        };
+        self.menu = Some(Menu::new(self.settings, self.presets, work_zero));
+    }
+
+    /// Moves the target by an increment in hundredths of a millimetre. Target
+    /// is counted in hundredths from the shown zero and rounded to whole
+    /// steps, so rounding doesn't add up over many detents.
+    fn jog_target(&mut self, rotation: Rotation, hundredths: u32) {
+        self.move_start_height = None;
+
+        let steps_per_mm = self.settings.motor_steps_per_mm as i64;
+        let zero = if self.work_coordinates {
+            self.work_zero as i64
+        } else {
+            0
+        };
+        let increment = match rotation {
+            Rotation::Clockwise => hundredths as i64,
+            Rotation::CounterClockwise => -(hundredths as i64),
+            Rotation::None => return,
+        };
+
+        let target = zero + jog(self.target_height as i64 - zero, increment, steps_per_mm);
+        self.target_height = target.max(0).min(self.max_height() as i64) as u32;
     }
 
     fn update_screen(
         &mut self,
         delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
-    ) -> Result<(), Error<SIA, SIB, LIM, STP, DIR, MEN, M1, M2>> {
-        if let Some(_) = self.current_height {
-            self.screen.update(
-                Frame::Height(self.target_height / self.motor_steps_per_mm),
-                delay,
-            )?;
-        } else {
-            self.screen.update(Frame::Calibrating, delay)?;
+    ) -> Result<(), MillError<Self>> {
+        if self.diagnostics {
+            let (sia, sib) = match self.encoder.levels() {
+                Ok((sia, sib)) => (Some(sia), Some(sib)),
+                Err(_) => (None, None),
+            };
+            let frame = Frame::Diagnostics(Diagnostics {
+                sia,
+                sib,
+                home: self.home_switch.is_high().ok(),
+                lower_limit: self.lower_limit_switch.is_high().ok(),
+                upper_limit: self.upper_limit_switch.is_high().ok(),
+                motor_enabled: self.motor.is_enabled(),
+                current_height: if self.state.is_calibrated() {
+                    Some(self.current_height)
+                } else {
+                    None
+                },
+                target_height: self.target_height,
+                last_fault: self.last_fault,
+            });
+            self.screen.update(frame, delay)?;
+            return Ok(());
+        }
+
+        if let Some(fault) = self.fault() {
+            self.screen.update(Frame::Fault(fault), delay)?;
+            return Ok(());
+        }
+
+        if let (MillState::Resume, Some(saved_height)) = (self.state, self.saved_height) {
+            let frame = Frame::Resume {
+                height: self.micrometres(saved_height),
+                resume: self.resume,
+            };
+            self.screen.update(frame, delay)?;
+            return Ok(());
         }
 
+        if let Some(menu) = &self.menu {
+            let frame = Frame::Menu {
+                label: menu.label(self.screen.language().strings()),
+                value: menu.value(),
+                editing: menu.is_editing(),
+            };
+            self.screen.update(frame, delay)?;
+            return Ok(());
+        }
+
+        if let MillState::Probing(_) = self.state {
+            self.screen.update(Frame::Probing, delay)?;
+            return Ok(());
+        }
+
+        let frame = match (self.state.is_calibrated(), self.move_start_height) {
+            (true, Some(move_start_height)) => Frame::Moving {
+                start: self.micrometres(move_start_height),
+                current: self.micrometres(self.current_height),
+                target: self.micrometres(self.target_height),
+                status: self.status(),
+            },
+            (true, None) if self.big_digits => Frame::BigHeight {
+                height: self.micrometres(self.target_height),
+                jog_increment: self.jog_increment,
+                status: self.status(),
+            },
+            (true, None) => Frame::Height {
+                height: self.micrometres(self.target_height),
+                jog_increment: self.jog_increment,
+                status: self.status(),
+            },
+            (false, _) => Frame::Calibrating,
+        };
+
+        self.screen.update(frame, delay)?;
         Ok(())
     }
+
+    fn status(&self) -> Status {
+        let coordinates = if self.work_coordinates {
+            Coordinates::Work
+        } else {
+            Coordinates::Machine
+        };
+        let preset = self
+            .presets
+            .iter()
+            .find(|preset| preset.height == Some(self.target_height))
+            .map(|preset| preset.name);
+
+        Status {
+            coordinates,
+            preset,
+        }
+    }
+
+    /// Converts height in motor steps to micrometres in the coordinates
+    /// shown on the screen.
+    fn micrometres(&self, steps: u32) -> i32 {
+        let steps = if self.work_coordinates {
+            steps as i64 - self.work_zero as i64
+        } else {
+            steps as i64
+        };
+        (steps * 1000 / self.settings.motor_steps_per_mm as i64) as i32
+    }
 }
 
-pub struct MillConfig<SIA, SIB, HOM, LIM, STP, DIR, MEN, M1, M2, DUR, RS, SEN, D4, D5, D6, D7>
-where
+pub struct MillConfig<
+    SIA,
+    SIB,
+    FIA,
+    FIB,
+    HOM,
+    BTN,
+    LLM,
+    ULM,
+    PRB,
+    PUP,
+    PDN,
+    MPA,
+    MPB,
+    X10,
+    X100,
+    STP,
+    DIR,
+    MEN,
+    M1,
+    M2,
+    DUR,
+    DSP,
+    BKL,
+    FLS,
+> where
     SIA: InputPin,
     SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
     HOM: InputPin,
-    LIM: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     MEN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
-    DUR: Copy,
-    RS: OutputPin,
-    SEN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
+    DUR: Copy + From<u8>,
+    DSP: DisplayBackend,
+    BKL: Backlight,
+    FLS: NorFlash,
 {
     pub encoder: RotaryEncoder<SIA, SIB>,
-    pub screen: Screen<RS, SEN, D4, D5, D6, D7>,
+    pub fine_encoder: RotaryEncoder<FIA, FIB>,
+    pub screen: Screen<DSP, BKL>,
     pub motor: StepperMotor<STP, DIR, MEN, M1, M2, DUR>,
     pub home_switch: HOM,
-    pub limit_switch: LIM,
+    pub lower_limit_switch: LLM,
+    pub upper_limit_switch: ULM,
+    // Touch plate, low when the bit touches it.
+    pub probe: PRB,
+    // Push-button of the encoder, opening the settings menu.
+    pub button: BTN,
+    pub foot_pedal: FootPedal<PUP, PDN>,
+    pub pendant: Pendant<MPA, MPB, X10, X100>,
+    // Settings and presets are saved there whenever they change. Loading them
+    // at boot is left to the application, as they are needed in this config.
+    pub storage: Storage<FLS>,
+    // Height in motor steps saved in the storage before the power was
+    // switched off. Operator is asked whether to resume it, or to home the
+    // lift.
+    pub saved_height: Option<u32>,
 
-    pub max_height: u32,
+    // Initial settings. Signal delay of the motor is replaced with the one
+    // from settings.
+    pub settings: Settings,
+    // Named target heights, recalled from the menu.
+    pub presets: [Preset; PRESET_COUNT],
     pub motor_steps_per_tick: u32,
-    pub motor_steps_per_mm: u32,
+    pub screen_refresh_ticks: u32,
+    // Whether to show height with digits two rows tall, readable from a
+    // distance.
+    pub big_digits: bool,
+    // Time the button has to be held to open the settings menu. Shorter
+    // clicks cycle the jog increment.
+    pub menu_hold_ms: u32,
+    pub homing: HomingConfig,
+    pub probing: ProbingConfig,
+}
+
+/// Homing sequence. Distances are in hundredths of a millimetre.
+#[derive(Debug, Copy, Clone)]
+pub struct HomingConfig {
+    // End of the travel at which the lift is homed. Its limit switch is used
+    // as the home switch, and maximum height is counted from there.
+    pub end: Limit,
+    // Whether to home again right after a limit switch trips outside homing,
+    // instead of waiting for the fault to be acknowledged. The fault is still
+    // shown on the diagnostics screen.
+    pub rehome_after_limit: bool,
+    // Distance the lift moves away from the switch after finding it for the
+    // first time.
+    pub back_off: u32,
+    // Signal delay of the slow approach, in units of the motor's signal
+    // delay. The switch is checked after every step of it, so the home is
+    // found where the switch trips, not past it.
+    pub slow_signal_delay: u8,
+    // Distance from the switch to zero height.
+    pub offset: u32,
+    // Maximum distance of every approach and maximum time of the whole
+    // sequence, in seconds, after which homing faults.
+    pub max_travel: u32,
+    pub timeout: u32,
+}
+
+/// Probing the work zero with a touch plate. Distances are in hundredths of
+/// a millimetre.
+#[derive(Debug, Copy, Clone)]
+pub struct ProbingConfig {
+    // Direction in which the bit moves towards the plate.
+    pub direction: Direction,
+    // Thickness of the plate, subtracted from the height at which the bit
+    // touches it to get the work zero.
+    pub plate_thickness: u32,
+    // Distance after which probing faults, when the bit doesn't touch the
+    // plate.
+    pub max_distance: u32,
+    // Distance the lift moves away from the plate after touching it.
+    pub retract: u32,
+    // Signal delay of the approach, in units of the motor's signal delay.
+    // The bit should stop as soon as it touches, without pressing the plate.
+    pub signal_delay: u8,
 }
 
-pub enum Error<SIA, SIB, LIM, STP, DIR, EN, M1, M2>
+/// Names the error type of a `Mill` without spelling out all of its pins,
+/// through `MillError`.
+pub trait MillErrorType {
+    type Error;
+}
+
+/// Error of the given `Mill`.
+pub type MillError<M> = <M as MillErrorType>::Error;
+
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        HOM,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        MEN,
+        M1,
+        M2,
+        DUR,
+        DSP,
+        BKL,
+        FLS,
+    > MillErrorType
+    for Mill<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        HOM,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        MEN,
+        M1,
+        M2,
+        DUR,
+        DSP,
+        BKL,
+        FLS,
+    >
 where
     SIA: InputPin,
     SIB: InputPin,
-    LIM: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    HOM: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
+    STP: OutputPin,
+    DIR: OutputPin,
+    MEN: OutputPin,
+    M1: OutputPin,
+    M2: OutputPin,
+    DUR: Copy + From<u8>,
+    DSP: DisplayBackend,
+    BKL: Backlight,
+    FLS: NorFlash,
+{
+    type Error = Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        MEN,
+        M1,
+        M2,
+        FLS,
+    >;
+}
+
+pub enum Error<
+    SIA,
+    SIB,
+    FIA,
+    FIB,
+    BTN,
+    LLM,
+    ULM,
+    PRB,
+    PUP,
+    PDN,
+    MPA,
+    MPB,
+    X10,
+    X100,
+    STP,
+    DIR,
+    EN,
+    M1,
+    M2,
+    FLS,
+> where
+    SIA: InputPin,
+    SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     EN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
+    FLS: NorFlash,
 {
     Encoder(rotary_encoder::Error<SIA, SIB>),
-    LimitSwitch(LIM::Error),
+    FineEncoder(rotary_encoder::Error<FIA, FIB>),
+    FootPedal(foot_pedal::Error<PUP, PDN>),
+    LowerLimitSwitch(LLM::Error),
+    UpperLimitSwitch(ULM::Error),
+    Probe(PRB::Error),
+    Button(BTN::Error),
     Motor(stepper_motor::Error<STP, DIR, EN, M1, M2>),
+    Pendant(pendant::Error<MPA, MPB, X10, X100>),
     ScreenUpdate(ScreenUpdateError),
     Sia(SIA::Error),
+    Storage(FLS::Error),
 }
 
-impl<SIA, SIB, LIM, STP, DIR, EN, M1, M2> From<rotary_encoder::Error<SIA, SIB>>
-    for Error<SIA, SIB, LIM, STP, DIR, EN, M1, M2>
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
+    Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
 where
     SIA: InputPin,
     SIB: InputPin,
-    LIM: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     EN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
+    FLS: NorFlash,
+{
+    pub fn fault(&self) -> Fault {
+        match self {
+            Error::Encoder(_) | Error::Sia(_) => Fault::Encoder,
+            Error::FineEncoder(_) => Fault::FineEncoder,
+            Error::FootPedal(_) => Fault::FootPedal,
+            Error::LowerLimitSwitch(_) | Error::UpperLimitSwitch(_) => Fault::LimitSwitch,
+            Error::Probe(_) => Fault::Probe,
+            Error::Button(_) => Fault::Button,
+            Error::Motor(_) => Fault::Motor,
+            Error::Pendant(_) => Fault::Pendant,
+            Error::ScreenUpdate(_) => Fault::Display,
+            Error::Storage(_) => Fault::Storage,
+        }
+    }
+}
+
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    > From<rotary_encoder::Error<SIA, SIB>>
+    for Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
+where
+    SIA: InputPin,
+    SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
+    STP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin,
+    M1: OutputPin,
+    M2: OutputPin,
+    FLS: NorFlash,
 {
     fn from(err: rotary_encoder::Error<SIA, SIB>) -> Self {
         Self::Encoder(err)
     }
 }
 
-impl<SIA, SIB, LIM, STP, DIR, EN, M1, M2> From<ScreenUpdateError>
-    for Error<SIA, SIB, LIM, STP, DIR, EN, M1, M2>
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    > From<foot_pedal::Error<PUP, PDN>>
+    for Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
+where
+    SIA: InputPin,
+    SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
+    STP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin,
+    M1: OutputPin,
+    M2: OutputPin,
+    FLS: NorFlash,
+{
+    fn from(err: foot_pedal::Error<PUP, PDN>) -> Self {
+        Self::FootPedal(err)
+    }
+}
+
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    > From<pendant::Error<MPA, MPB, X10, X100>>
+    for Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
+where
+    SIA: InputPin,
+    SIB: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
+    STP: OutputPin,
+    DIR: OutputPin,
+    EN: OutputPin,
+    M1: OutputPin,
+    M2: OutputPin,
+    FLS: NorFlash,
+{
+    fn from(err: pendant::Error<MPA, MPB, X10, X100>) -> Self {
+        Self::Pendant(err)
+    }
+}
+
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    > From<ScreenUpdateError>
+    for Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
 where
     SIA: InputPin,
     SIB: InputPin,
-    LIM: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     EN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
+    FLS: NorFlash,
 {
     fn from(err: ScreenUpdateError) -> Self {
         Self::ScreenUpdate(err)
     }
 }
 
-impl<SIA, SIB, LIM, STP, DIR, EN, M1, M2> From<stepper_motor::Error<STP, DIR, EN, M1, M2>>
-    for Error<SIA, SIB, LIM, STP, DIR, EN, M1, M2>
+impl<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    > From<stepper_motor::Error<STP, DIR, EN, M1, M2>>
+    for Error<
+        SIA,
+        SIB,
+        FIA,
+        FIB,
+        BTN,
+        LLM,
+        ULM,
+        PRB,
+        PUP,
+        PDN,
+        MPA,
+        MPB,
+        X10,
+        X100,
+        STP,
+        DIR,
+        EN,
+        M1,
+        M2,
+        FLS,
+    >
 where
     SIA: InputPin,
     SIB: InputPin,
-    LIM: InputPin,
+    FIA: InputPin,
+    FIB: InputPin,
+    BTN: InputPin,
+    LLM: InputPin,
+    ULM: InputPin,
+    PRB: InputPin,
+    PUP: InputPin,
+    PDN: InputPin,
+    MPA: InputPin,
+    MPB: InputPin,
+    X10: InputPin,
+    X100: InputPin,
     STP: OutputPin,
     DIR: OutputPin,
     EN: OutputPin,
     M1: OutputPin,
     M2: OutputPin,
+    FLS: NorFlash,
 {
     fn from(err: stepper_motor::Error<STP, DIR, EN, M1, M2>) -> Self {
         Self::Motor(err)
     }
 }
+
+/// Moves height given in motor steps by a signed increment in hundredths of a
+/// millimetre, landing on the nearest hundredth. Increments finer than a step
+/// move the height by one step.
+fn jog(height: i64, increment: i64, steps_per_mm: i64) -> i64 {
+    let hundredths = round_div(height * 100, steps_per_mm) + increment;
+    let jogged = round_div(hundredths * steps_per_mm, 100);
+    if increment > 0 {
+        jogged.max(height + 1)
+    } else {
+        jogged.min(height - 1)
+    }
+}
+
+/// Divides rounding halves away from zero. Divisor has to be positive.
+fn round_div(dividend: i64, divisor: i64) -> i64 {
+    (dividend + dividend.signum() * divisor / 2) / divisor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backlight::GpioBacklight,
+        display::{Geometry, Hd44780Display},
+        locale::Language,
+        mock::{NoBus, NoDelay, Pin, RamFlash, FLASH_SIZE},
+        screen::ScreenConfig,
+        stepper_motor::{Duration, Mode, StepperMotorConfig},
+        unit::Unit,
+    };
+
+    type TestMill<'a> = Mill<
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        Pin,
+        u8,
+        Hd44780Display<NoBus>,
+        GpioBacklight<Pin>,
+        &'a mut RamFlash,
+    >;
+
+    /// Mill with 200 steps per millimetre and 100 mm of travel, homed at the
+    /// given end. Switches are released and the probe doesn't touch.
+    fn mill(flash: &mut RamFlash, end: Limit) -> TestMill<'_> {
+        let motor = StepperMotor::new(StepperMotorConfig {
+            step: Pin(false),
+            dir: Pin(false),
+            enable: Pin(false),
+            m1: Pin(false),
+            m2: Pin(false),
+            mode: Mode::FullStep,
+            signal_delay: Duration::Us(1),
+        });
+        let screen = Screen::new(ScreenConfig {
+            display: Hd44780Display::new(NoBus, &mut NoDelay).ok().unwrap(),
+            geometry: Geometry::Lcd16x2,
+            language: Language::English,
+            unit: Unit::Millimetres { decimals: 2 },
+            backlight: None,
+            dimmed_brightness: 0,
+            dim_after: None,
+            off_after: None,
+        });
+
+        Mill::new(
+            MillConfig {
+                encoder: RotaryEncoder::new(Pin(false), Pin(false)),
+                fine_encoder: RotaryEncoder::new(Pin(false), Pin(false)),
+                screen,
+                motor: motor.ok().unwrap(),
+                home_switch: Pin(true),
+                lower_limit_switch: Pin(true),
+                upper_limit_switch: Pin(true),
+                probe: Pin(true),
+                button: Pin(false),
+                foot_pedal: FootPedal::new(Pin(false), Pin(false)),
+                pendant: Pendant::new(
+                    RotaryEncoder::new(Pin(false), Pin(false)),
+                    Pin(false),
+                    Pin(false),
+                ),
+                storage: Storage::new(flash, 0, FLASH_SIZE as u32),
+                saved_height: None,
+
+                settings: Settings {
+                    signal_delay: 1,
+                    motor_steps_per_mm: 200,
+                    max_height: 100,
+                    language: Language::English,
+                    unit: Unit::Millimetres { decimals: 2 },
+                },
+                presets: [Preset::new("Preset"); PRESET_COUNT],
+                motor_steps_per_tick: 1,
+                screen_refresh_ticks: 1,
+                big_digits: false,
+                menu_hold_ms: 500,
+                homing: HomingConfig {
+                    end,
+                    rehome_after_limit: false,
+                    back_off: 200,
+                    slow_signal_delay: 5,
+                    offset: 100,
+                    max_travel: 10_000,
+                    timeout: 120,
+                },
+                probing: ProbingConfig {
+                    direction: Direction::Up,
+                    plate_thickness: 1000,
+                    max_distance: 2000,
+                    retract: 200,
+                    signal_delay: 5,
+                },
+            },
+            &mut NoDelay,
+        )
+    }
+
+    #[test]
+    fn presets_keep_their_position_when_max_height_grows_with_upper_homing() {
+        let mut flash = RamFlash::new();
+        let mut mill = mill(&mut flash, Limit::Upper);
+        // 5 mm below the upper end, which stays where it is.
+        mill.presets[0].height = Some(19_000);
+
+        mill.apply_settings(Settings {
+            max_height: 120,
+            ..mill.settings
+        });
+        assert_eq!(mill.presets[0].height, Some(23_000));
+        assert_eq!(mill.presets[1].height, None);
+    }
+
+    #[test]
+    fn presets_stay_when_max_height_changes_with_lower_homing() {
+        let mut flash = RamFlash::new();
+        let mut mill = mill(&mut flash, Limit::Lower);
+        mill.presets[0].height = Some(19_000);
+
+        mill.apply_settings(Settings {
+            max_height: 120,
+            ..mill.settings
+        });
+        assert_eq!(mill.presets[0].height, Some(19_000));
+    }
+
+    #[test]
+    fn homing_wakes_the_screen() {
+        let mut flash = RamFlash::new();
+        let mut mill = mill(&mut flash, Limit::Lower);
+        mill.idle_seconds = 60;
+
+        mill.home(HomingPhase::FastApproach, &mut NoDelay)
+            .ok()
+            .unwrap();
+        assert_eq!(mill.idle_seconds, 0);
+    }
+
+    #[test]
+    fn limit_switch_fault_wakes_the_screen() {
+        let mut flash = RamFlash::new();
+        let mut mill = mill(&mut flash, Limit::Lower);
+        mill.state = MillState::Idle;
+        mill.idle_seconds = 60;
+
+        mill.handle_limit_switch_interrupt(Limit::Upper, &mut NoDelay)
+            .ok()
+            .unwrap();
+        assert_eq!(mill.fault(), Some(Fault::UpperLimit));
+        assert_eq!(mill.idle_seconds, 0);
+    }
+
+    #[test]
+    fn touching_probe_keeps_the_saved_height() {
+        let mut flash = RamFlash::new();
+        {
+            let mut mill = mill(&mut flash, Limit::Lower);
+            mill.state = MillState::Idle;
+            mill.saved_height = Some(1000);
+            mill.probe = Pin(false);
+
+            mill.handle_event(Event::Probe, &mut NoDelay).ok().unwrap();
+            assert_eq!(mill.fault(), Some(Fault::ProbeTouching));
+            assert_eq!(mill.saved_height, Some(1000));
+        }
+
+        assert!(flash.bytes.iter().all(|byte| *byte == 0xff));
+    }
+
+    #[test]
+    fn round_div_rounds_halves_away_from_zero() {
+        assert_eq!(round_div(14, 10), 1);
+        assert_eq!(round_div(15, 10), 2);
+        assert_eq!(round_div(-14, 10), -1);
+        assert_eq!(round_div(-15, 10), -2);
+        assert_eq!(round_div(0, 10), 0);
+    }
+
+    #[test]
+    fn jog_lands_on_the_nearest_hundredth() {
+        // 200 steps per millimetre, 2 steps per hundredth.
+        assert_eq!(jog(0, 100, 200), 200);
+        assert_eq!(jog(201, 1, 200), 204);
+        assert_eq!(jog(201, -1, 200), 200);
+        // Below the work zero.
+        assert_eq!(jog(-201, -1, 200), -204);
+        assert_eq!(jog(-201, 1, 200), -200);
+    }
+
+    #[test]
+    fn jog_finer_than_a_step_moves_one_step() {
+        // 50 steps per millimetre, 2 hundredths per step.
+        assert_eq!(jog(10, 1, 50), 11);
+        assert_eq!(jog(10, -1, 50), 9);
+        assert_eq!(jog(10, 10, 50), 15);
+        // Pendant at x1 with less than a step per hundredth.
+        assert_eq!(jog(0, 1, 40), 1);
+        assert_eq!(jog(1, 1, 40), 2);
+    }
+}
@@ -0,0 +1,226 @@
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Language {
+    Polish,
+    English,
+    German,
+}
+
+impl Language {
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::Polish => &POLISH,
+            Language::English => &ENGLISH,
+            Language::German => &GERMAN,
+        }
+    }
+
+    /// Name of the language in that language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Polish => "Polski",
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+}
+
+/// Every text displayed on the screen. Each string must fit in a single line
+/// of the display. Characters missing in the display ROM are drawn with custom
+/// glyphs, see `glyph` module.
+pub struct Strings {
+    pub current_height: &'static str,
+    pub jog_increment: &'static str,
+    pub calibrating: &'static str,
+    pub probing: &'static str,
+    pub resume_at: &'static str,
+    pub yes: &'static str,
+    pub rehome: &'static str,
+    pub welcome: &'static str,
+
+    // Settings menu.
+    pub presets: &'static str,
+    pub recall: &'static str,
+    pub work_zero: &'static str,
+    pub set_zero: &'static str,
+    pub probe: &'static str,
+    pub coordinates: &'static str,
+    pub machine: &'static str,
+    pub work: &'static str,
+    pub motor: &'static str,
+    pub display: &'static str,
+    pub save: &'static str,
+    pub cancel: &'static str,
+    pub back: &'static str,
+    pub signal_delay: &'static str,
+    pub steps_per_mm: &'static str,
+    pub max_height: &'static str,
+    pub language: &'static str,
+    pub unit: &'static str,
+
+    // Faults.
+    pub fault: &'static str,
+    pub acknowledge: &'static str,
+    pub encoder_fault: &'static str,
+    pub fine_encoder_fault: &'static str,
+    pub foot_pedal_fault: &'static str,
+    pub limit_switch_fault: &'static str,
+    pub motor_fault: &'static str,
+    pub pendant_fault: &'static str,
+    pub display_fault: &'static str,
+    pub homing_timeout_fault: &'static str,
+    pub homing_travel_fault: &'static str,
+    pub lower_limit_fault: &'static str,
+    pub upper_limit_fault: &'static str,
+    pub storage_fault: &'static str,
+    pub probe_fault: &'static str,
+    pub probe_touching_fault: &'static str,
+    pub probe_distance_fault: &'static str,
+    pub button_fault: &'static str,
+}
+
+static POLISH: Strings = Strings {
+    current_height: "Obecna wysokość:",
+    jog_increment: "Krok:",
+    calibrating: "Kalibracja...",
+    probing: "Pomiar sondą...",
+    resume_at: "Wznów",
+    yes: "Tak",
+    rehome: "Bazowanie",
+    welcome: "Witaj!",
+
+    presets: "Pamięć wysokości",
+    recall: "Przywołaj",
+    work_zero: "Zero robocze",
+    set_zero: "Ustaw zero tu",
+    probe: "Zero sondą",
+    coordinates: "Współrzędne",
+    machine: "Maszynowe",
+    work: "Robocze",
+    motor: "Silnik",
+    display: "Wyświetlacz",
+    save: "Zapisz",
+    cancel: "Anuluj",
+    back: "Wróć",
+    signal_delay: "Opóźnienie kroku",
+    steps_per_mm: "Kroki na mm",
+    max_height: "Maks. wysokość",
+    language: "Język",
+    unit: "Jednostka",
+
+    fault: "Błąd",
+    acknowledge: "Wciśnij przycisk",
+    encoder_fault: "Enkoder",
+    fine_encoder_fault: "Enkoder dokładny",
+    foot_pedal_fault: "Pedał",
+    limit_switch_fault: "Krańcówka",
+    motor_fault: "Silnik",
+    pendant_fault: "Pilot",
+    display_fault: "Wyświetlacz",
+    homing_timeout_fault: "Czas bazowania",
+    homing_travel_fault: "Zakres bazowania",
+    lower_limit_fault: "Dolna krańcówka",
+    upper_limit_fault: "Górna krańcówka",
+    storage_fault: "Pamięć flash",
+    probe_fault: "Sonda",
+    probe_touching_fault: "Sonda zwarta",
+    probe_distance_fault: "Brak styku sondy",
+    button_fault: "Przycisk",
+};
+
+static ENGLISH: Strings = Strings {
+    current_height: "Current height:",
+    jog_increment: "Step:",
+    calibrating: "Calibrating...",
+    probing: "Probing...",
+    resume_at: "Resume",
+    yes: "Yes",
+    rehome: "Re-home",
+    welcome: "Welcome!",
+
+    presets: "Presets",
+    recall: "Recall",
+    work_zero: "Work zero",
+    set_zero: "Set zero here",
+    probe: "Probe zero",
+    coordinates: "Coordinates",
+    machine: "Machine",
+    work: "Work",
+    motor: "Motor",
+    display: "Display",
+    save: "Save",
+    cancel: "Cancel",
+    back: "Back",
+    signal_delay: "Step delay",
+    steps_per_mm: "Steps per mm",
+    max_height: "Max height",
+    language: "Language",
+    unit: "Unit",
+
+    fault: "Error",
+    acknowledge: "Press the button",
+    encoder_fault: "Encoder",
+    fine_encoder_fault: "Fine encoder",
+    foot_pedal_fault: "Foot pedal",
+    limit_switch_fault: "Limit switch",
+    motor_fault: "Motor",
+    pendant_fault: "Pendant",
+    display_fault: "Display",
+    homing_timeout_fault: "Homing timeout",
+    homing_travel_fault: "Homing travel",
+    lower_limit_fault: "Lower limit hit",
+    upper_limit_fault: "Upper limit hit",
+    storage_fault: "Flash memory",
+    probe_fault: "Probe",
+    probe_touching_fault: "Probe touching",
+    probe_distance_fault: "No probe contact",
+    button_fault: "Button",
+};
+
+static GERMAN: Strings = Strings {
+    current_height: "Aktuelle Höhe:",
+    jog_increment: "Schritt:",
+    calibrating: "Kalibrierung...",
+    probing: "Antasten...",
+    resume_at: "Weiter",
+    yes: "Ja",
+    rehome: "Referenzfahrt",
+    welcome: "Willkommen!",
+
+    presets: "Speicherplätze",
+    recall: "Abrufen",
+    work_zero: "Werkstücknull",
+    set_zero: "Null hier setzen",
+    probe: "Null antasten",
+    coordinates: "Koordinaten",
+    machine: "Maschine",
+    work: "Werkstück",
+    motor: "Motor",
+    display: "Anzeige",
+    save: "Speichern",
+    cancel: "Abbrechen",
+    back: "Zurück",
+    signal_delay: "Schrittpause",
+    steps_per_mm: "Schritte pro mm",
+    max_height: "Max. Höhe",
+    language: "Sprache",
+    unit: "Einheit",
+
+    fault: "Fehler",
+    acknowledge: "Taste drücken",
+    encoder_fault: "Drehgeber",
+    fine_encoder_fault: "Feindrehgeber",
+    foot_pedal_fault: "Fußpedal",
+    limit_switch_fault: "Endschalter",
+    motor_fault: "Motor",
+    pendant_fault: "Handrad",
+    display_fault: "Anzeige",
+    homing_timeout_fault: "Referenz Zeit",
+    homing_travel_fault: "Referenz Weg",
+    lower_limit_fault: "Untere Endlage",
+    upper_limit_fault: "Obere Endlage",
+    storage_fault: "Flash-Speicher",
+    probe_fault: "Taster",
+    probe_touching_fault: "Taster berührt",
+    probe_distance_fault: "Kein Kontakt",
+    button_fault: "Knopf",
+};
@@ -4,33 +4,88 @@
 use panic_semihosting as _;
 
 use core::cell::RefCell;
-use cortex_m::interrupt::{free as interrupt_free, Mutex};
+use cortex_m::{
+    interrupt::{free as interrupt_free, Mutex},
+    peripheral::{DCB, DWT},
+};
 use cortex_m_rt::entry;
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash,
+};
 use mill::{
+    backlight::PwmBacklight,
+    display::{Geometry, Hd44780Display},
+    fault::Fault,
+    foot_pedal::{Direction, FootPedal},
+    frame::Frame,
+    hd44780::FourBitBus,
+    locale::Language,
+    pendant::Pendant,
+    preset::{Preset, PRESET_COUNT},
     rotary_encoder::RotaryEncoder,
-    screen::{Frame, Screen, ScreenConfig},
+    screen::{Screen, ScreenConfig},
+    settings::Settings,
+    state::Limit,
     stepper_motor::{Duration, Mode, StepperMotor, StepperMotorConfig},
-    Mill, MillConfig,
+    storage::Storage,
+    unit::Unit,
+    Error, HomingConfig, Mill, MillConfig, ProbingConfig,
 };
 use stm32f4xx_hal::{
     delay::Delay,
+    flash::FlashExt,
     gpio::{
-        gpioa::{PA1, PA10, PA11, PA12, PA2, PA8, PA9},
-        gpiob::{PB0, PB1, PB12, PB13, PB14, PB15, PB6, PB7},
-        Edge, ExtiPin, Input, Output, PullDown, PushPull,
+        gpioa::{PA0, PA1, PA10, PA11, PA12, PA15, PA2, PA3, PA4, PA7, PA8, PA9},
+        gpiob::{PB0, PB1, PB10, PB12, PB13, PB14, PB15, PB3, PB4, PB5, PB6, PB7, PB8, PB9},
+        Edge, ExtiPin, Input, Output, PullDown, PullUp, PushPull,
     },
     interrupt,
-    pac::{CorePeripherals, Interrupt, Peripherals, NVIC},
+    pac::{CorePeripherals, Interrupt, Peripherals, FLASH, NVIC, TIM3},
     prelude::*,
+    pwm::{self, PwmChannels, C1},
+    rcc::Clocks,
     rtc::Rtc,
 };
 
+// Size of the display in characters.
+const GEOMETRY: Geometry = Geometry::Lcd16x2;
+
+// Backlight is dimmed to `DIMMED_BRIGHTNESS` percent after `DIM_AFTER` seconds
+// without any input or motion, and switched off after `OFF_AFTER` seconds.
+const DIMMED_BRIGHTNESS: u8 = 20;
+const DIM_AFTER: Option<u32> = Some(5 * 60);
+const OFF_AFTER: Option<u32> = Some(30 * 60);
+
+// Language of texts displayed on the screen.
+const LANGUAGE: Language = Language::Polish;
+
+// Unit in which height is displayed.
+const UNIT: Unit = Unit::Millimetres { decimals: 2 };
+
+// Display height with digits two rows tall, so it's readable from a distance.
+const BIG_DIGITS: bool = false;
+
+// Holding the encoder button this long opens the settings menu, shorter
+// clicks cycle the jog increment.
+const MENU_HOLD_MS: u32 = 500;
+
 // If you change this, you should propably change `MM_STEPS` too.
 const MOTOR_MODE: Mode = Mode::FullStep;
 
 // How many steps is one milimeter.
 const MM_STEPS: u32 = 200;
 
+// Maximum height of the lift in milimetres.
+const MAX_HEIGHT: u32 = 48;
+
+// Target heights which can be saved and recalled from the menu, by name.
+const PRESETS: [Preset; PRESET_COUNT] = [
+    Preset::new("Preset 1"),
+    Preset::new("Preset 2"),
+    Preset::new("Preset 3"),
+    Preset::new("Preset 4"),
+];
+
 // Main loop doesn't rotate motor to the specified height at once, as rotation
 // executes inside `interrupt_free` block, so it would block any user
 // interaction until motor stops rotating and it would be frustrating. So
@@ -42,6 +97,48 @@ const STEPS_PER_LOOP: u32 = 1;
 // Interval between signals send to the stepper motor driver.
 const SIGNAL_DELAY: u8 = 1;
 
+// Homing finds the limit switch at `HOMING_END`, backs off by
+// `HOMING_BACK_OFF` and finds it again slowly, with
+// `HOMING_SLOW_SIGNAL_DELAY`. Then it moves by `HOME_OFFSET`, which is the end
+// of the travel. If the switch isn't found within `HOMING_MAX_TRAVEL` or
+// `HOMING_TIMEOUT` seconds, homing faults. Distances are in hundredths of a
+// millimetre.
+const HOMING_END: Limit = Limit::Lower;
+const HOMING_BACK_OFF: u32 = 200;
+const HOMING_SLOW_SIGNAL_DELAY: u8 = 5;
+const HOME_OFFSET: u32 = 100;
+const HOMING_MAX_TRAVEL: u32 = 6000;
+const HOMING_TIMEOUT: u32 = 120;
+
+// Hitting a limit switch outside homing is a fault. It has to be acknowledged
+// before the lift is homed again, unless `REHOME_AFTER_LIMIT` is set.
+const REHOME_AFTER_LIMIT: bool = false;
+
+// Probing moves the bit `PROBE_DIRECTION` slowly, with `PROBE_SIGNAL_DELAY`,
+// until it touches the plate, and sets the work zero `PLATE_THICKNESS` below.
+// Then it moves back by `PROBE_RETRACT`. If the bit doesn't touch the plate
+// within `PROBE_MAX_DISTANCE`, probing faults. Distances are in hundredths of a
+// millimetre.
+const PROBE_DIRECTION: Direction = Direction::Up;
+const PLATE_THICKNESS: u32 = 1000;
+const PROBE_MAX_DISTANCE: u32 = 2000;
+const PROBE_RETRACT: u32 = 200;
+const PROBE_SIGNAL_DELAY: u8 = 5;
+
+// While the lift moves, screen is refreshed every this many `interrupt_free`
+// blocks of the main loop, as refreshing it takes much longer than rotating the
+// motor by `STEPS_PER_LOOP`.
+const SCREEN_REFRESH_LOOPS: u32 = 100;
+
+// Settings and presets are kept in flash sectors 1 and 2, which are left out of
+// the program in `memory.x`. Records are written to both in turn, so the
+// current one survives while the other sector is erased. Sectors are 16K, so
+// erasing one takes a fraction of a second.
+const SETTINGS_SECTOR: u8 = 1;
+const SETTINGS_OFFSET: usize = 0x4000;
+const SETTINGS_SECTOR_SIZE: usize = 0x4000;
+const SETTINGS_SIZE: usize = 2 * SETTINGS_SECTOR_SIZE;
+
 static DELAY: Mutex<RefCell<Option<Delay>>> = Mutex::new(RefCell::new(None));
 static MILL: Mutex<
     RefCell<
@@ -49,29 +146,47 @@ static MILL: Mutex<
             Mill<
                 PB0<Input<PullDown>>,
                 PB1<Input<PullDown>>,
+                PB3<Input<PullDown>>,
+                PB4<Input<PullDown>>,
                 PA1<Input<PullDown>>,
+                PA15<Input<PullDown>>,
                 PA2<Input<PullDown>>,
+                PA7<Input<PullDown>>,
+                PA0<Input<PullUp>>,
+                PA3<Input<PullDown>>,
+                PA4<Input<PullDown>>,
+                PB5<Input<PullDown>>,
+                PB8<Input<PullDown>>,
+                PB9<Input<PullDown>>,
+                PB10<Input<PullDown>>,
                 PB6<Output<PushPull>>,
                 PB7<Output<PushPull>>,
                 PA10<Output<PushPull>>,
                 PA11<Output<PushPull>>,
                 PA12<Output<PushPull>>,
                 u8,
-                PB12<Output<PushPull>>,
-                PB13<Output<PushPull>>,
-                PB14<Output<PushPull>>,
-                PB15<Output<PushPull>>,
-                PA8<Output<PushPull>>,
-                PA9<Output<PushPull>>,
+                Hd44780Display<
+                    FourBitBus<
+                        PB12<Output<PushPull>>,
+                        PB13<Output<PushPull>>,
+                        PB14<Output<PushPull>>,
+                        PB15<Output<PushPull>>,
+                        PA8<Output<PushPull>>,
+                        PA9<Output<PushPull>>,
+                    >,
+                >,
+                PwmBacklight<PwmChannels<TIM3, C1>>,
+                SettingsFlash,
             >,
         >,
     >,
 > = Mutex::new(RefCell::new(None));
 static RTC: Mutex<RefCell<Option<Rtc>>> = Mutex::new(RefCell::new(None));
+static CLOCK: Mutex<RefCell<Option<Clock>>> = Mutex::new(RefCell::new(None));
 
 #[entry]
 fn main() -> ! {
-    let core_peripherals = CorePeripherals::take().unwrap();
+    let mut core_peripherals = CorePeripherals::take().unwrap();
     let mut peripherals = Peripherals::take().unwrap();
 
     let rcc = peripherals.RCC.constrain();
@@ -80,82 +195,201 @@ fn main() -> ! {
     let gpiob = peripherals.GPIOB.split();
     let clocks = rcc.cfgr.freeze();
     let mut delay = Delay::new(core_peripherals.SYST, clocks);
+    let clock = Clock::new(&mut core_peripherals.DCB, &mut core_peripherals.DWT, clocks);
+
+    // Compiled in settings are used until some are saved, or when the saved
+    // ones can't be read. Saved height is offered to be resumed only if the
+    // lift stood still when the power was switched off.
+    let mut storage = Storage::new(SettingsFlash(peripherals.FLASH), 0, SETTINGS_SIZE as u32);
+    let mut settings = Settings {
+        signal_delay: SIGNAL_DELAY,
+        motor_steps_per_mm: MM_STEPS,
+        max_height: MAX_HEIGHT,
+        language: LANGUAGE,
+        unit: UNIT,
+    };
+    let mut presets = PRESETS;
+    let mut saved_height = None;
+    if let Ok(Some(record)) = storage.load() {
+        settings = record.settings;
+        saved_height = record.position;
+        for (preset, height) in presets.iter_mut().zip(record.presets.iter()) {
+            preset.height = *height;
+        }
+    }
 
     let mut sia = gpiob.pb0.into_pull_down_input();
     sia.make_interrupt_source(&mut syscfg);
     sia.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
     sia.enable_interrupt(&mut peripherals.EXTI);
 
+    let mut fine_sia = gpiob.pb3.into_pull_down_input();
+    fine_sia.make_interrupt_source(&mut syscfg);
+    fine_sia.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
+    fine_sia.enable_interrupt(&mut peripherals.EXTI);
+
     let mut home_switch = gpioa.pa1.into_pull_down_input();
     home_switch.make_interrupt_source(&mut syscfg);
     home_switch.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
     home_switch.enable_interrupt(&mut peripherals.EXTI);
 
-    let mut limit_switch = gpioa.pa2.into_pull_down_input();
-    limit_switch.make_interrupt_source(&mut syscfg);
-    limit_switch.trigger_on_edge(&mut peripherals.EXTI, Edge::RISING);
-    limit_switch.enable_interrupt(&mut peripherals.EXTI);
+    // Limit switches read low when tripped, like the home switch, so their
+    // interrupts fire on the falling edge.
+    let mut lower_limit_switch = gpioa.pa2.into_pull_down_input();
+    lower_limit_switch.make_interrupt_source(&mut syscfg);
+    lower_limit_switch.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
+    lower_limit_switch.enable_interrupt(&mut peripherals.EXTI);
+
+    let mut upper_limit_switch = gpioa.pa7.into_pull_down_input();
+    upper_limit_switch.make_interrupt_source(&mut syscfg);
+    upper_limit_switch.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
+    upper_limit_switch.enable_interrupt(&mut peripherals.EXTI);
+
+    let mut button = gpioa.pa15.into_pull_down_input();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut peripherals.EXTI, Edge::RISING_FALLING);
+    button.enable_interrupt(&mut peripherals.EXTI);
+
+    let mut pendant_a = gpiob.pb5.into_pull_down_input();
+    pendant_a.make_interrupt_source(&mut syscfg);
+    pendant_a.trigger_on_edge(&mut peripherals.EXTI, Edge::FALLING);
+    pendant_a.enable_interrupt(&mut peripherals.EXTI);
 
     unsafe {
         NVIC::unmask(Interrupt::EXTI0);
         NVIC::unmask(Interrupt::EXTI1);
         NVIC::unmask(Interrupt::EXTI2);
+        NVIC::unmask(Interrupt::EXTI3);
+        NVIC::unmask(Interrupt::EXTI9_5);
+        NVIC::unmask(Interrupt::EXTI15_10);
     };
 
     let rtc = Rtc::new(peripherals.RTC, 255, 127, false, &mut peripherals.PWR);
 
-    let mut screen = Screen::new(
-        ScreenConfig {
-            d7: gpioa.pa9.into_push_pull_output(),
-            d6: gpioa.pa8.into_push_pull_output(),
-            d5: gpiob.pb15.into_push_pull_output(),
-            d4: gpiob.pb14.into_push_pull_output(),
-            en: gpiob.pb13.into_push_pull_output(),
-            rs: gpiob.pb12.into_push_pull_output(),
-        },
+    // Motor is set up first, so it can be disabled when the rest fails.
+    let mut motor = StepperMotor::new(StepperMotorConfig {
+        dir: gpiob.pb7.into_push_pull_output(),
+        step: gpiob.pb6.into_push_pull_output(),
+        m2: gpioa.pa12.into_push_pull_output(),
+        m1: gpioa.pa11.into_push_pull_output(),
+        enable: gpioa.pa10.into_push_pull_output(),
+
+        mode: MOTOR_MODE,
+        signal_delay: Duration::Ms(SIGNAL_DELAY),
+    });
+
+    let display = match Hd44780Display::new(
+        FourBitBus::new(
+            gpiob.pb12.into_push_pull_output(),
+            gpiob.pb13.into_push_pull_output(),
+            gpiob.pb14.into_push_pull_output(),
+            gpiob.pb15.into_push_pull_output(),
+            gpioa.pa8.into_push_pull_output(),
+            gpioa.pa9.into_push_pull_output(),
+        ),
         &mut delay,
-    )
-    .ok()
-    .unwrap();
+    ) {
+        Ok(display) => display,
+        // Fault can't be shown without the display, so the motor is only
+        // disabled.
+        Err(_) => {
+            if let Ok(motor) = motor.as_mut() {
+                motor.disable().ok();
+            }
+            halt();
+        }
+    };
+
+    let mut screen = Screen::new(ScreenConfig {
+        display,
+        geometry: GEOMETRY,
+
+        language: settings.language,
+        unit: settings.unit,
+
+        backlight: Some(PwmBacklight::new(pwm::tim3(
+            peripherals.TIM3,
+            gpioa.pa6.into_alternate_af2(),
+            clocks,
+            1.khz(),
+        ))),
+        dimmed_brightness: DIMMED_BRIGHTNESS,
+        dim_after: DIM_AFTER,
+        off_after: OFF_AFTER,
+    });
 
-    screen.update(Frame::Welcome, &mut delay).ok().unwrap();
+    let welcome = screen.update(Frame::Welcome, &mut delay);
     delay.delay_ms(5000u16);
 
-    let mill = Mill::new(
+    let motor = match motor {
+        Ok(motor) => motor,
+        Err(_) => {
+            screen.update(Frame::Fault(Fault::Motor), &mut delay).ok();
+            halt();
+        }
+    };
+
+    let mut mill = Mill::new(
         MillConfig {
             encoder: RotaryEncoder::new(sia, gpiob.pb1.into_pull_down_input()),
+            fine_encoder: RotaryEncoder::new(fine_sia, gpiob.pb4.into_pull_down_input()),
 
             screen,
 
-            motor: StepperMotor::new(StepperMotorConfig {
-                dir: gpiob.pb7.into_push_pull_output(),
-                step: gpiob.pb6.into_push_pull_output(),
-                m2: gpioa.pa12.into_push_pull_output(),
-                m1: gpioa.pa11.into_push_pull_output(),
-                enable: gpioa.pa10.into_push_pull_output(),
+            motor,
 
-                mode: MOTOR_MODE,
-                signal_delay: Duration::Ms(SIGNAL_DELAY),
-            })
-            .ok()
-            .unwrap(),
-
-            limit_switch,
+            lower_limit_switch,
+            upper_limit_switch,
+            probe: gpioa.pa0.into_pull_up_input(),
             home_switch,
+            button,
+
+            foot_pedal: FootPedal::new(
+                gpioa.pa3.into_pull_down_input(),
+                gpioa.pa4.into_pull_down_input(),
+            ),
+            pendant: Pendant::new(
+                RotaryEncoder::new(pendant_a, gpiob.pb8.into_pull_down_input()),
+                gpiob.pb9.into_pull_down_input(),
+                gpiob.pb10.into_pull_down_input(),
+            ),
+            storage,
+            saved_height,
 
-            max_height: 48 * MM_STEPS,
+            settings,
+            presets,
             motor_steps_per_tick: STEPS_PER_LOOP,
-            motor_steps_per_mm: MM_STEPS,
+            screen_refresh_ticks: SCREEN_REFRESH_LOOPS,
+            big_digits: BIG_DIGITS,
+            menu_hold_ms: MENU_HOLD_MS,
+            homing: HomingConfig {
+                end: HOMING_END,
+                rehome_after_limit: REHOME_AFTER_LIMIT,
+                back_off: HOMING_BACK_OFF,
+                slow_signal_delay: HOMING_SLOW_SIGNAL_DELAY,
+                offset: HOME_OFFSET,
+                max_travel: HOMING_MAX_TRAVEL,
+                timeout: HOMING_TIMEOUT,
+            },
+            probing: ProbingConfig {
+                direction: PROBE_DIRECTION,
+                plate_thickness: PLATE_THICKNESS,
+                max_distance: PROBE_MAX_DISTANCE,
+                retract: PROBE_RETRACT,
+                signal_delay: PROBE_SIGNAL_DELAY,
+            },
         },
         &mut delay,
-    )
-    .ok()
-    .unwrap();
+    );
+    if let Err(err) = welcome {
+        mill.enter_fault(Error::ScreenUpdate(err), &mut delay);
+    }
 
     interrupt_free(|cs| {
         MILL.borrow(cs).replace(Some(mill));
         DELAY.borrow(cs).replace(Some(delay));
         RTC.borrow(cs).replace(Some(rtc));
+        CLOCK.borrow(cs).replace(Some(clock));
     });
 
     loop {
@@ -163,10 +397,16 @@ fn main() -> ! {
             let mut option = MILL.borrow(cs).borrow_mut();
             let mut delay = DELAY.borrow(cs).borrow_mut();
             let mut rtc = RTC.borrow(cs).borrow_mut();
-            if let (Some(mill), Some(delay), Some(rtc)) =
-                (option.as_mut(), delay.as_mut(), rtc.as_mut())
-            {
-                mill.tick(delay, rtc).ok().unwrap();
+            let mut clock = CLOCK.borrow(cs).borrow_mut();
+            if let (Some(mill), Some(delay), Some(rtc), Some(clock)) = (
+                option.as_mut(),
+                delay.as_mut(),
+                rtc.as_mut(),
+                clock.as_mut(),
+            ) {
+                if let Err(err) = mill.tick(delay, rtc, clock.now()) {
+                    mill.enter_fault(err, delay);
+                }
             }
         });
     }
@@ -184,7 +424,9 @@ fn EXTI0() {
                 return;
             }
 
-            mill.handle_sia_interrupt(delay, rtc).ok().unwrap();
+            if let Err(err) = mill.handle_sia_interrupt(delay, rtc) {
+                mill.enter_fault(err, delay);
+            }
             mill.encoder.sia.clear_interrupt_pending_bit();
         }
     });
@@ -200,7 +442,9 @@ fn EXTI1() {
                 return;
             }
 
-            mill.handle_home_switch_interrupt(delay).ok().unwrap();
+            if let Err(err) = mill.handle_home_switch_interrupt(delay) {
+                mill.enter_fault(err, delay);
+            }
             mill.home_switch.clear_interrupt_pending_bit();
         }
     })
@@ -212,12 +456,174 @@ fn EXTI2() {
         let mut mill = MILL.borrow(cs).borrow_mut();
         let mut delay = DELAY.borrow(cs).borrow_mut();
         if let (Some(mill), Some(delay)) = (mill.as_mut(), delay.as_mut()) {
-            if !mill.limit_switch.check_interrupt() {
+            if !mill.lower_limit_switch.check_interrupt() {
+                return;
+            }
+
+            if let Err(err) = mill.handle_limit_switch_interrupt(Limit::Lower, delay) {
+                mill.enter_fault(err, delay);
+            }
+            mill.lower_limit_switch.clear_interrupt_pending_bit();
+        }
+    });
+}
+
+#[interrupt]
+fn EXTI3() {
+    interrupt_free(|cs| {
+        let mut mill = MILL.borrow(cs).borrow_mut();
+        let mut delay = DELAY.borrow(cs).borrow_mut();
+        let mut rtc = RTC.borrow(cs).borrow_mut();
+        if let (Some(mill), Some(delay), Some(rtc)) = (mill.as_mut(), delay.as_mut(), rtc.as_mut())
+        {
+            if !mill.fine_encoder.sia.check_interrupt() {
+                return;
+            }
+
+            if let Err(err) = mill.handle_fine_encoder_interrupt(delay, rtc) {
+                mill.enter_fault(err, delay);
+            }
+            mill.fine_encoder.sia.clear_interrupt_pending_bit();
+        }
+    });
+}
+
+#[interrupt]
+fn EXTI9_5() {
+    interrupt_free(|cs| {
+        let mut mill = MILL.borrow(cs).borrow_mut();
+        let mut delay = DELAY.borrow(cs).borrow_mut();
+        let mut rtc = RTC.borrow(cs).borrow_mut();
+        if let (Some(mill), Some(delay), Some(rtc)) = (mill.as_mut(), delay.as_mut(), rtc.as_mut())
+        {
+            // Line is shared by the pendant and the upper limit switch.
+            if mill.upper_limit_switch.check_interrupt() {
+                if let Err(err) = mill.handle_limit_switch_interrupt(Limit::Upper, delay) {
+                    mill.enter_fault(err, delay);
+                }
+                mill.upper_limit_switch.clear_interrupt_pending_bit();
+            }
+
+            if mill.pendant.encoder.sia.check_interrupt() {
+                if let Err(err) = mill.handle_pendant_interrupt(delay, rtc) {
+                    mill.enter_fault(err, delay);
+                }
+                mill.pendant.encoder.sia.clear_interrupt_pending_bit();
+            }
+        }
+    });
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    interrupt_free(|cs| {
+        let mut mill = MILL.borrow(cs).borrow_mut();
+        let mut delay = DELAY.borrow(cs).borrow_mut();
+        let mut clock = CLOCK.borrow(cs).borrow_mut();
+        if let (Some(mill), Some(delay), Some(clock)) =
+            (mill.as_mut(), delay.as_mut(), clock.as_mut())
+        {
+            if !mill.button.check_interrupt() {
                 return;
             }
 
-            mill.handle_limit_switch_interrupt(delay).ok().unwrap();
-            mill.limit_switch.clear_interrupt_pending_bit();
+            if let Err(err) = mill.handle_button_interrupt(delay, clock.now()) {
+                mill.enter_fault(err, delay);
+            }
+            mill.button.clear_interrupt_pending_bit();
         }
     });
 }
+
+/// Stops the firmware when the mill can't be set up, so it doesn't run
+/// without a display or a motor it can control.
+fn halt() -> ! {
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Milliseconds since boot, counted with the cycle counter of the core. At
+/// the default 16 MHz the counter wraps around every 268 seconds, so the clock
+/// has to be read more often than that, which the main loop does.
+struct Clock {
+    cycles_per_millisecond: u32,
+    last_cycles: u32,
+    // Cycles since the last whole millisecond.
+    cycles: u32,
+    milliseconds: u32,
+}
+
+impl Clock {
+    fn new(dcb: &mut DCB, dwt: &mut DWT, clocks: Clocks) -> Self {
+        dcb.enable_trace();
+        dwt.enable_cycle_counter();
+
+        Self {
+            cycles_per_millisecond: clocks.sysclk().0 / 1000,
+            last_cycles: DWT::get_cycle_count(),
+            cycles: 0,
+            milliseconds: 0,
+        }
+    }
+
+    fn now(&mut self) -> u32 {
+        let cycles = DWT::get_cycle_count();
+        let elapsed = cycles.wrapping_sub(self.last_cycles) as u64 + self.cycles as u64;
+        self.last_cycles = cycles;
+
+        let per_millisecond = self.cycles_per_millisecond as u64;
+        self.milliseconds = self
+            .milliseconds
+            .wrapping_add((elapsed / per_millisecond) as u32);
+        self.cycles = (elapsed % per_millisecond) as u32;
+        self.milliseconds
+    }
+}
+
+/// Settings sectors of the flash. Offsets are relative to the start of the
+/// first one.
+struct SettingsFlash(FLASH);
+
+impl ErrorType for SettingsFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for SettingsFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len())?;
+        let start = SETTINGS_OFFSET + offset as usize;
+        bytes.copy_from_slice(&self.0.read()[start..start + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        SETTINGS_SIZE
+    }
+}
+
+impl NorFlash for SettingsFlash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SETTINGS_SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to)?;
+
+        let mut flash = self.0.unlocked();
+        for page in (from..to).step_by(Self::ERASE_SIZE) {
+            let sector = SETTINGS_SECTOR + (page as usize / Self::ERASE_SIZE) as u8;
+            flash.erase(sector).map_err(|_| NorFlashErrorKind::Other)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len())?;
+        self.0
+            .unlocked()
+            .program(SETTINGS_OFFSET + offset as usize, bytes.iter())
+            .map_err(|_| NorFlashErrorKind::Other)
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    locale::{Language, Strings},
+    preset::{Preset, PRESET_COUNT},
+    rotary_encoder::Rotation,
+    settings::Settings,
+    unit::Unit,
+};
+
+const MAIN: [Entry; 6] = [
+    Entry::Presets,
+    Entry::WorkZero,
+    Entry::Motor,
+    Entry::Display,
+    Entry::Save,
+    Entry::Cancel,
+];
+const PRESETS: [Entry; PRESET_COUNT + 1] = [
+    Entry::Preset(0),
+    Entry::Preset(1),
+    Entry::Preset(2),
+    Entry::Preset(3),
+    Entry::Back,
+];
+const WORK_ZERO: [Entry; 4] = [
+    Entry::SetZero,
+    Entry::Probe,
+    Entry::Coordinates,
+    Entry::Back,
+];
+const MOTOR: [Entry; 4] = [
+    Entry::SignalDelay,
+    Entry::StepsPerMm,
+    Entry::MaxHeight,
+    Entry::Back,
+];
+const DISPLAY: [Entry; 3] = [Entry::Language, Entry::Unit, Entry::Back];
+
+const LANGUAGES: [Language; 3] = [Language::Polish, Language::English, Language::German];
+const UNITS: [Unit; 6] = [
+    Unit::Millimetres { decimals: 0 },
+    Unit::Millimetres { decimals: 1 },
+    Unit::Millimetres { decimals: 2 },
+    Unit::Inches { decimals: 3 },
+    Unit::Inches { decimals: 4 },
+    Unit::FractionalInches { denominator: 64 },
+];
+
+const MAX_STEPS_PER_MM: u32 = 10_000;
+const MAX_HEIGHT: u32 = 999;
+
+/// Settings menu. Rotation of the encoder selects an entry, or changes the
+/// value of the edited one. Pressing the button opens the selected submenu or
+/// starts and finishes editing. Changes are made on a copy of the settings,
+/// so they don't take effect until they are saved.
+///
+/// Presets are edited the same way, by choosing whether to recall the preset
+/// or to save the target in it. Both close the menu right away, like setting
+/// the work zero and switching coordinates.
+pub struct Menu {
+    settings: Settings,
+    presets: [Preset; PRESET_COUNT],
+    // Work zero in motor steps, when heights are shown in work coordinates.
+    work_zero: Option<u32>,
+    preset_choice: PresetChoice,
+    entries: &'static [Entry],
+    position: usize,
+    // Position of the submenu in the main menu, so it's selected after going
+    // back.
+    parent_position: usize,
+    editing: bool,
+}
+
+impl Menu {
+    pub fn new(
+        settings: Settings,
+        presets: [Preset; PRESET_COUNT],
+        work_zero: Option<u32>,
+    ) -> Self {
+        Self {
+            settings,
+            presets,
+            work_zero,
+            preset_choice: PresetChoice::Recall,
+            entries: &MAIN,
+            position: 0,
+            parent_position: 0,
+            editing: false,
+        }
+    }
+
+    pub fn rotate(&mut self, rotation: Rotation) {
+        if self.editing {
+            self.edit(rotation);
+            return;
+        }
+
+        let count = self.entries.len();
+        self.position = match rotation {
+            Rotation::Clockwise => (self.position + 1) % count,
+            Rotation::CounterClockwise => (self.position + count - 1) % count,
+            Rotation::None => self.position,
+        };
+    }
+
+    /// Returns action to take when menu is closed.
+    pub fn press(&mut self) -> Option<Action> {
+        if self.editing {
+            self.editing = false;
+            return match (self.selected(), self.preset_choice) {
+                (Entry::Preset(slot), PresetChoice::Recall) => Some(Action::Recall(slot)),
+                (Entry::Preset(slot), PresetChoice::Save) => Some(Action::SavePreset(slot)),
+                _ => None,
+            };
+        }
+
+        match self.selected() {
+            Entry::Presets => self.open(&PRESETS),
+            Entry::WorkZero => self.open(&WORK_ZERO),
+            Entry::SetZero => return Some(Action::SetZero),
+            Entry::Probe => return Some(Action::Probe),
+            Entry::Coordinates => return Some(Action::SwitchCoordinates),
+            Entry::Preset(slot) => {
+                // Empty presets have nothing to recall, so saving is offered first.
+                self.preset_choice = match self.presets[slot].height {
+                    Some(_) => PresetChoice::Recall,
+                    None => PresetChoice::Save,
+                };
+                self.editing = true;
+            }
+            Entry::Motor => self.open(&MOTOR),
+            Entry::Display => self.open(&DISPLAY),
+            Entry::Back => {
+                self.entries = &MAIN;
+                self.position = self.parent_position;
+            }
+            Entry::Save => return Some(Action::Save(self.settings)),
+            Entry::Cancel => return Some(Action::Cancel),
+            _ => self.editing = true,
+        }
+
+        None
+    }
+
+    pub fn selected(&self) -> Entry {
+        self.entries[self.position]
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    /// Label of the selected entry. Presets are labelled with their names.
+    pub fn label(&self, strings: &Strings) -> &'static str {
+        match self.selected() {
+            Entry::Preset(slot) => self.presets[slot].name,
+            entry => entry.label(strings),
+        }
+    }
+
+    /// Value of the selected entry, if it's a setting or a preset. Preset
+    /// heights are shown in the coordinates of the screen.
+    pub fn value(&self) -> Option<Value> {
+        match self.selected() {
+            Entry::Preset(_) if self.editing => Some(Value::PresetChoice(self.preset_choice)),
+            Entry::Preset(slot) => Some(Value::Height(self.presets[slot].height.map(|height| {
+                let steps = height as i64 - self.work_zero.unwrap_or(0) as i64;
+                (steps * 1000 / self.settings.motor_steps_per_mm as i64) as i32
+            }))),
+            Entry::Coordinates => Some(Value::Coordinates(match self.work_zero {
+                Some(_) => Coordinates::Work,
+                None => Coordinates::Machine,
+            })),
+            Entry::SignalDelay => Some(Value::Number(self.settings.signal_delay as u32)),
+            Entry::StepsPerMm => Some(Value::Number(self.settings.motor_steps_per_mm)),
+            Entry::MaxHeight => Some(Value::Milimetres(self.settings.max_height)),
+            Entry::Language => Some(Value::Language(self.settings.language)),
+            Entry::Unit => Some(Value::Unit(self.settings.unit)),
+            _ => None,
+        }
+    }
+
+    fn open(&mut self, entries: &'static [Entry]) {
+        self.parent_position = self.position;
+        self.entries = entries;
+        self.position = 0;
+    }
+
+    fn edit(&mut self, rotation: Rotation) {
+        let settings = &mut self.settings;
+        match self.entries[self.position] {
+            Entry::SignalDelay => {
+                settings.signal_delay =
+                    adjust(settings.signal_delay as u32, rotation, 1, u8::MAX as u32) as u8
+            }
+            Entry::StepsPerMm => {
+                settings.motor_steps_per_mm =
+                    adjust(settings.motor_steps_per_mm, rotation, 1, MAX_STEPS_PER_MM)
+            }
+            Entry::MaxHeight => {
+                settings.max_height = adjust(settings.max_height, rotation, 1, MAX_HEIGHT)
+            }
+            Entry::Language => settings.language = cycle(&LANGUAGES, settings.language, rotation),
+            Entry::Unit => settings.unit = cycle(&UNITS, settings.unit, rotation),
+            Entry::Preset(_) => {
+                self.preset_choice = cycle(
+                    &[PresetChoice::Recall, PresetChoice::Save],
+                    self.preset_choice,
+                    rotation,
+                )
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Entry {
+    Presets,
+    Preset(usize),
+    WorkZero,
+    SetZero,
+    Probe,
+    Coordinates,
+    Motor,
+    Display,
+    Save,
+    Cancel,
+    Back,
+    SignalDelay,
+    StepsPerMm,
+    MaxHeight,
+    Language,
+    Unit,
+}
+
+impl Entry {
+    pub fn label(self, strings: &Strings) -> &'static str {
+        match self {
+            Entry::Presets => strings.presets,
+            // Presets are labelled with their names by the menu.
+            Entry::Preset(_) => "",
+            Entry::WorkZero => strings.work_zero,
+            Entry::SetZero => strings.set_zero,
+            Entry::Probe => strings.probe,
+            Entry::Coordinates => strings.coordinates,
+            Entry::Motor => strings.motor,
+            Entry::Display => strings.display,
+            Entry::Save => strings.save,
+            Entry::Cancel => strings.cancel,
+            Entry::Back => strings.back,
+            Entry::SignalDelay => strings.signal_delay,
+            Entry::StepsPerMm => strings.steps_per_mm,
+            Entry::MaxHeight => strings.max_height,
+            Entry::Language => strings.language,
+            Entry::Unit => strings.unit,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Value {
+    Number(u32),
+    Milimetres(u32),
+    Language(Language),
+    Unit(Unit),
+    /// Height of a preset in micrometres, `None` when it's empty.
+    Height(Option<i32>),
+    PresetChoice(PresetChoice),
+    Coordinates(Coordinates),
+}
+
+/// Coordinates in which heights are shown. Work coordinates are counted from
+/// the work zero, machine ones from the homed end.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Coordinates {
+    Machine,
+    Work,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PresetChoice {
+    Recall,
+    Save,
+}
+
+pub enum Action {
+    Save(Settings),
+    Cancel,
+    /// Move to the height of the preset in the slot.
+    Recall(usize),
+    /// Save the target height in the preset slot.
+    SavePreset(usize),
+    /// Set the work zero at the current height.
+    SetZero,
+    /// Set the work zero by probing the touch plate.
+    Probe,
+    /// Switch between machine and work coordinates.
+    SwitchCoordinates,
+}
+
+fn adjust(value: u32, rotation: Rotation, min: u32, max: u32) -> u32 {
+    match rotation {
+        Rotation::Clockwise => value.saturating_add(1).min(max),
+        Rotation::CounterClockwise => value.saturating_sub(1).max(min),
+        Rotation::None => value,
+    }
+}
+
+/// Selects next or previous item, wrapping around. Items missing from the
+/// list are replaced with the first one.
+fn cycle<T: PartialEq + Copy>(items: &[T], current: T, rotation: Rotation) -> T {
+    let count = items.len();
+    let position = match items.iter().position(|item| *item == current) {
+        Some(position) => position,
+        None => return items[0],
+    };
+
+    match rotation {
+        Rotation::Clockwise => items[(position + 1) % count],
+        Rotation::CounterClockwise => items[(position + count - 1) % count],
+        Rotation::None => current,
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{display::Error, hd44780::DataBus};
+use core::convert::Infallible;
+use embedded_hal::{
+    blocking::delay::{DelayMs, DelayUs},
+    digital::v2::{InputPin, OutputPin},
+};
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash,
+};
+
+pub const PAGE_SIZE: usize = 256;
+pub const FLASH_SIZE: usize = 4 * PAGE_SIZE;
+
+pub struct NoDelay;
+
+impl DelayMs<u8> for NoDelay {
+    fn delay_ms(&mut self, _ms: u8) {}
+}
+
+impl DelayUs<u8> for NoDelay {
+    fn delay_us(&mut self, _us: u8) {}
+}
+
+impl DelayUs<u16> for NoDelay {
+    fn delay_us(&mut self, _us: u16) {}
+}
+
+/// Pin which reads the level it was set to.
+pub struct Pin(pub bool);
+
+impl InputPin for Pin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0)
+    }
+}
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0 = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0 = true;
+        Ok(())
+    }
+}
+
+/// Bus of a display which isn't connected.
+pub struct NoBus;
+
+impl DataBus for NoBus {
+    fn write_nibble(
+        &mut self,
+        _nibble: u8,
+        _data: bool,
+        _delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Flash kept in RAM. Writes can only clear bits, like on the real one.
+pub struct RamFlash {
+    pub bytes: [u8; FLASH_SIZE],
+    pub erases: usize,
+    // Number of bytes the next write stops after, as if power failed.
+    pub torn_write: Option<usize>,
+}
+
+impl RamFlash {
+    pub fn new() -> Self {
+        Self {
+            bytes: [0xff; FLASH_SIZE],
+            erases: 0,
+            torn_write: None,
+        }
+    }
+}
+
+impl ErrorType for RamFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for RamFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len())?;
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.bytes[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+}
+
+impl NorFlash for RamFlash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to)?;
+        self.bytes[from as usize..to as usize]
+            .iter_mut()
+            .for_each(|byte| *byte = 0xff);
+        self.erases += 1;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len())?;
+        let length = self.torn_write.take().unwrap_or(bytes.len());
+        let offset = offset as usize;
+        for (flash, byte) in self.bytes[offset..].iter_mut().zip(&bytes[..length]) {
+            *flash &= *byte;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::display::{Buffer, DisplayBackend, Error, Geometry};
+use embedded_graphics::{
+    mono_font::{
+        iso_8859_2::{FONT_6X10, FONT_8X13},
+        MonoTextStyle,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 64;
+
+/// SSD1306 128x64 OLED display. Text grid is the same as on the character
+/// LCD, so on 16x2 geometry every character gets a big cell. 40 columns
+/// don't fit even with the smaller font, so the text is cut.
+pub struct Ssd1306Display<DI: WriteOnlyDataCommand> {
+    display: Ssd1306<DI, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>,
+    shadow: Buffer,
+}
+
+impl<DI: WriteOnlyDataCommand> Ssd1306Display<DI> {
+    pub fn new(interface: DI) -> Result<Self, Error> {
+        let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+            .into_buffered_graphics_mode();
+        display.init().map_err(|_| Error)?;
+        display.flush().map_err(|_| Error)?;
+
+        Ok(Self {
+            display,
+            shadow: Buffer::new(Geometry::Lcd16x2),
+        })
+    }
+
+    fn draw_cell(
+        &mut self,
+        geometry: Geometry,
+        row: usize,
+        column: usize,
+        character: char,
+    ) -> Result<(), Error> {
+        let cell_width = WIDTH / geometry.columns() as u32;
+        let cell_height = HEIGHT / geometry.rows() as u32;
+        let top_left = Point::new(
+            (column as u32 * cell_width) as i32,
+            (row as u32 * cell_height) as i32,
+        );
+
+        Rectangle::new(top_left, Size::new(cell_width, cell_height))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+            .draw(&mut self.display)
+            .map_err(|_| Error)?;
+
+        // Blocks are drawn as rectangles, so progress bars and big digits are
+        // continuous.
+        if let Some(fifths) = block_width(character) {
+            return self.fill(top_left, Size::new(cell_width * fifths / 5, cell_height));
+        }
+
+        let bar = Size::new(cell_width, cell_height * 3 / 8);
+        let bottom_bar = top_left + Point::new(0, (cell_height - bar.height) as i32);
+        match character {
+            '▀' => return self.fill(top_left, bar),
+            '▄' => return self.fill(bottom_bar, bar),
+            '≡' => {
+                self.fill(top_left, bar)?;
+                return self.fill(bottom_bar, bar);
+            }
+            _ => {}
+        }
+
+        // Font covers Latin-2, so only arrows need replacing.
+        let character = match character {
+            '↑' => '^',
+            '↓' => 'v',
+            '→' => '>',
+            '←' => '<',
+            _ => character,
+        };
+
+        let mut text = [0; 4];
+        let text = character.encode_utf8(&mut text);
+        let font = if cell_width >= 8 {
+            &FONT_8X13
+        } else {
+            &FONT_6X10
+        };
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        let position = top_left
+            + Point::new(
+                0,
+                (cell_height as i32 - font.character_size.height as i32) / 2,
+            );
+
+        Text::with_baseline(text, position, style, Baseline::Top)
+            .draw(&mut self.display)
+            .map_err(|_| Error)?;
+
+        Ok(())
+    }
+
+    fn fill(&mut self, top_left: Point, size: Size) -> Result<(), Error> {
+        Rectangle::new(top_left, size)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut self.display)
+            .map_err(|_| Error)
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> DisplayBackend for Ssd1306Display<DI> {
+    fn draw(
+        &mut self,
+        buffer: &Buffer,
+        _delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+    ) -> Result<(), Error> {
+        // Cells have different size on every geometry, so everything has
+        // to be redrawn when it changes.
+        if self.shadow.geometry() != buffer.geometry() {
+            DrawTarget::clear(&mut self.display, BinaryColor::Off).map_err(|_| Error)?;
+            self.shadow = Buffer::new(buffer.geometry());
+        }
+
+        let mut changed = false;
+        for row in 0..buffer.rows() {
+            for column in 0..buffer.columns() {
+                let character = buffer.get(row, column);
+                if self.shadow.get(row, column) != character {
+                    self.draw_cell(buffer.geometry(), row, column, character)?;
+                    self.shadow.set(row, column, character);
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            self.display.flush().map_err(|_| Error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns how many fifths of a cell width the block character fills.
+fn block_width(character: char) -> Option<u32> {
+    match character {
+        '▏' => Some(1),
+        '▎' => Some(2),
+        '▍' => Some(3),
+        '▋' => Some(4),
+        '█' => Some(5),
+        _ => None,
+    }
+}
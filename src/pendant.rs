@@ -0,0 +1,45 @@
+use crate::rotary_encoder::{self, RotaryEncoder, Rotation};
+use embedded_hal::digital::v2::InputPin;
+
+/// Manual pulse generator (handwheel) with x1/x10/x100 selector switch.
+pub struct Pendant<A: InputPin, B: InputPin, X10: InputPin, X100: InputPin> {
+    pub encoder: RotaryEncoder<A, B>,
+    x10: X10,
+    x100: X100,
+}
+
+impl<A: InputPin, B: InputPin, X10: InputPin, X100: InputPin> Pendant<A, B, X10, X100> {
+    pub fn new(encoder: RotaryEncoder<A, B>, x10: X10, x100: X100) -> Self {
+        Self { encoder, x10, x100 }
+    }
+
+    pub fn update(&mut self) -> Result<Rotation, Error<A, B, X10, X100>> {
+        Ok(self.encoder.update()?)
+    }
+
+    /// Reads selector switch. When selector is in x1 position, none of the
+    /// pins is high.
+    pub fn multiplier(&self) -> Result<u32, Error<A, B, X10, X100>> {
+        if self.x100.is_high().map_err(|err| Error::X100(err))? {
+            Ok(100)
+        } else if self.x10.is_high().map_err(|err| Error::X10(err))? {
+            Ok(10)
+        } else {
+            Ok(1)
+        }
+    }
+}
+
+pub enum Error<A: InputPin, B: InputPin, X10: InputPin, X100: InputPin> {
+    Encoder(rotary_encoder::Error<A, B>),
+    X10(X10::Error),
+    X100(X100::Error),
+}
+
+impl<A: InputPin, B: InputPin, X10: InputPin, X100: InputPin> From<rotary_encoder::Error<A, B>>
+    for Error<A, B, X10, X100>
+{
+    fn from(err: rotary_encoder::Error<A, B>) -> Self {
+        Self::Encoder(err)
+    }
+}
@@ -0,0 +1,16 @@
+/// Number of preset slots.
+pub const PRESET_COUNT: usize = 4;
+
+/// Named target height which can be recalled from the menu.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Preset {
+    pub name: &'static str,
+    /// Height in motor steps, `None` until something is saved in the slot.
+    pub height: Option<u32>,
+}
+
+impl Preset {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, height: None }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    display::Buffer,
+    locale::{Language, Strings},
+    unit::{self, Unit},
+};
+use core::fmt;
+
+// Progress bar cells, from empty to full.
+const PROGRESS_BAR: [char; 6] = [' ', '▏', '▎', '▍', '▋', '█'];
+
+// Two rows tall digits, like on DRO displays. `≡` has both top and bottom
+// bars, so middle bar can be drawn in the upper row.
+const BIG_DIGITS: [[&str; 2]; 10] = [
+    ["█▀█", "█▄█"],
+    ["▀█ ", "▄█▄"],
+    ["≡≡█", "█▄▄"],
+    ["≡≡█", "▄▄█"],
+    ["█▄█", "  █"],
+    ["█≡≡", "▄▄█"],
+    ["█≡≡", "█▄█"],
+    ["▀▀█", "  █"],
+    ["█≡█", "█▄█"],
+    ["█≡█", "▄▄█"],
+];
+const BIG_DIGIT_WIDTH: usize = 3;
+
+/// Contents of the screen. Built-in frames implement it, and applications can
+/// implement it to show their own screens with `Mill::show`.
+pub trait Render {
+    fn render(&self, canvas: &mut Canvas) -> Result<(), fmt::Error>;
+}
+
+/// Text canvas on which frames are rendered. Besides writing text, it knows
+/// the language and the unit selected for the screen.
+pub struct Canvas<'a> {
+    buffer: &'a mut Buffer,
+    language: Language,
+    unit: Unit,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(buffer: &'a mut Buffer, language: Language, unit: Unit) -> Self {
+        Self {
+            buffer,
+            language,
+            unit,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.buffer.rows()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.buffer.columns()
+    }
+
+    /// Displays with more than two rows leave one free row between the
+    /// heights and the progress bar, and draw big digits in the middle.
+    pub fn is_tall(&self) -> bool {
+        self.rows() >= 4
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn strings(&self) -> &'static Strings {
+        self.language.strings()
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// Characters outside of the display are ignored.
+    pub fn set(&mut self, row: usize, column: usize, character: char) {
+        self.buffer.set(row, column, character);
+    }
+
+    /// Text that doesn't fit in the row is cut off.
+    pub fn write_str(&mut self, row: usize, column: usize, text: &str) {
+        self.buffer.write_str(row, column, text);
+    }
+
+    pub fn write_centered(&mut self, row: usize, text: &str) {
+        let column = self.columns().saturating_sub(text.chars().count()) / 2;
+        self.write_str(row, column, text);
+    }
+
+    pub fn write_right_aligned(&mut self, row: usize, text: &str) {
+        let column = self.columns().saturating_sub(text.chars().count());
+        self.write_str(row, column, text);
+    }
+
+    /// Writes height given in micrometres in the unit of the screen, centered.
+    pub fn write_height(&mut self, row: usize, height: i32) -> Result<(), fmt::Error> {
+        let content = unit::format_height(height, self.unit)?;
+        self.write_centered(row, &content);
+        Ok(())
+    }
+
+    /// Draws progress given in permille, filling every row cell column by
+    /// column.
+    pub fn write_progress_bar(&mut self, row: usize, progress: u32) {
+        let columns = self.columns();
+        let cell_levels = PROGRESS_BAR.len() - 1;
+        let filled = progress.min(1000) as usize * columns * cell_levels / 1000;
+
+        for column in 0..columns {
+            let level = filled.saturating_sub(column * cell_levels).min(cell_levels);
+            self.set(row, column, PROGRESS_BAR[level]);
+        }
+    }
+
+    /// Draws digits of the height over two rows, starting at the given one.
+    /// Other characters, like the decimal point or the unit, are drawn
+    /// normally in the bottom row. Digits are separated with a space, unless
+    /// it wouldn't fit.
+    pub fn write_big_height(&mut self, row: usize, height: i32) -> Result<(), fmt::Error> {
+        let content = unit::format_height(height, self.unit)?;
+        let columns = self.columns();
+
+        let digits = content.chars().filter(char::is_ascii_digit).count();
+        let others = content.chars().count() - digits;
+        let gap = if digits * (BIG_DIGIT_WIDTH + 1) + others <= columns + 1 {
+            1
+        } else {
+            0
+        };
+
+        let mut column = 0;
+        for character in content.chars() {
+            match character.to_digit(10) {
+                Some(digit) => {
+                    let [top, bottom] = BIG_DIGITS[digit as usize];
+                    self.write_str(row, column, top);
+                    self.write_str(row + 1, column, bottom);
+                    column += BIG_DIGIT_WIDTH + gap;
+                }
+                None => {
+                    self.set(row + 1, column, character);
+                    column += 1;
+                }
+            }
+
+            if column >= columns {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -19,6 +19,14 @@ impl<SIA: InputPin, SIB: InputPin> RotaryEncoder<SIA, SIB> {
             Ok(Rotation::CounterClockwise)
         }
     }
+
+    /// Returns whether SIA and SIB are high.
+    pub fn levels(&self) -> Result<(bool, bool), Error<SIA, SIB>> {
+        let sia = self.sia.is_high().map_err(|err| Error::Sia(err))?;
+        let sib = self.sib.is_high().map_err(|err| Error::Sib(err))?;
+
+        Ok((sia, sib))
+    }
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -1,110 +1,134 @@
-use arrayvec::ArrayString;
-use core::fmt;
-use embedded_hal::{
-    blocking::delay::{DelayMs, DelayUs},
-    digital::v2::OutputPin,
-};
-use fmt::Write;
-use hd44780_driver::{
-    bus::FourBitBus, error::Error as HD44780Error, Cursor, CursorBlink, Display, DisplayMode,
-    HD44780,
+use crate::{
+    backlight::Backlight,
+    display::{self, Buffer, DisplayBackend, Geometry},
+    locale::Language,
+    render::{Canvas, Render},
+    unit::Unit,
 };
+use core::fmt;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+// Brightness of the backlight while the display is in use, in percent.
+const FULL_BRIGHTNESS: u8 = 100;
 
-pub struct Screen<RS, EN, D4, D5, D6, D7>
-where
-    RS: OutputPin,
-    EN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
-{
-    hd44780: HD44780<FourBitBus<RS, EN, D4, D5, D6, D7>>,
+pub struct Screen<D: DisplayBackend, BL: Backlight> {
+    display: D,
+    geometry: Geometry,
+    language: Language,
+    unit: Unit,
+
+    backlight: Option<BL>,
+    // Brightness set last time, so backlight is changed only when needed. It's
+    // unknown until the first frame is drawn.
+    brightness: Option<u8>,
+    dimmed_brightness: u8,
+    dim_after: Option<u32>,
+    off_after: Option<u32>,
 }
 
-impl<RS, EN, D4, D5, D6, D7> Screen<RS, EN, D4, D5, D6, D7>
-where
-    RS: OutputPin,
-    EN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
-{
-    pub fn new(
-        config: ScreenConfig<RS, EN, D4, D5, D6, D7>,
-        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
-    ) -> Result<Self, HD44780Error> {
+impl<D: DisplayBackend, BL: Backlight> Screen<D, BL> {
+    pub fn new(config: ScreenConfig<D, BL>) -> Self {
         let ScreenConfig {
-            rs,
-            en,
-            d4,
-            d5,
-            d6,
-            d7,
+            display,
+            geometry,
+            language,
+            unit,
+            backlight,
+            dimmed_brightness,
+            dim_after,
+            off_after,
             ..
         } = config;
 
-        let mut hd44780 = HD44780::new_4bit(rs, en, d4, d5, d6, d7, delay)?;
+        Self {
+            display,
+            geometry,
+            language,
+            unit,
 
-        hd44780.set_display_mode(
-            DisplayMode {
-                cursor_blink: CursorBlink::Off,
-                cursor_visibility: Cursor::Invisible,
-                display: Display::On,
-            },
-            delay,
-        )?;
+            backlight,
+            brightness: None,
+            dimmed_brightness,
+            dim_after,
+            off_after,
+        }
+    }
 
-        Ok(Self { hd44780 })
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
     }
 
-    pub fn update(
-        &mut self,
-        frame: Frame,
-        delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
-    ) -> Result<(), ScreenUpdateError> {
-        self.hd44780.clear(delay)?;
-
-        match frame {
-            Frame::Height(height) => {
-                self.hd44780.write_str("Obecna wysokosc:", delay)?;
-                self.write_height_line(height, delay)?;
-                Ok(())
-            }
-            Frame::Calibrating => {
-                self.hd44780.write_str("Kalibracja...", delay)?;
-                Ok(())
-            }
-            Frame::Welcome => {
-                self.hd44780.write_str("Witaj!", delay)?;
-                Ok(())
-            }
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    pub fn set_unit(&mut self, unit: Unit) {
+        self.unit = unit;
+    }
+
+    /// Dims or switches off the backlight, depending on how many seconds
+    /// passed since the last input or motion.
+    pub fn set_idle_time(&mut self, seconds: u32) -> Result<(), ScreenUpdateError> {
+        let brightness = if self
+            .off_after
+            .map_or(false, |off_after| seconds >= off_after)
+        {
+            0
+        } else if self
+            .dim_after
+            .map_or(false, |dim_after| seconds >= dim_after)
+        {
+            self.dimmed_brightness
+        } else {
+            FULL_BRIGHTNESS
+        };
+
+        self.set_brightness(brightness)
+    }
+
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), ScreenUpdateError> {
+        if self.brightness == Some(brightness) {
+            return Ok(());
+        }
+
+        if let Some(backlight) = self.backlight.as_mut() {
+            backlight
+                .set_brightness(brightness)
+                .map_err(ScreenUpdateError::Backlight)?;
         }
+        self.brightness = Some(brightness);
+        Ok(())
     }
 
-    fn write_height_line(
+    pub fn update(
         &mut self,
-        height: u32,
+        frame: impl Render,
         delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
     ) -> Result<(), ScreenUpdateError> {
-        let content = height_to_string(height)?;
-        self.hd44780.set_cursor_pos(40 + 5, delay)?;
-        self.hd44780.write_str(&content, delay)?;
-        Ok(())
-    }
-}
+        if self.brightness.is_none() {
+            self.set_brightness(FULL_BRIGHTNESS)?;
+        }
 
-fn height_to_string(height: u32) -> Result<ArrayString<[u8; 5]>, fmt::Error> {
-    let mut content = ArrayString::<[_; 5]>::new();
-    write!(content, "{:02}mm", height)?;
+        let mut buffer = Buffer::new(self.geometry);
+        frame.render(&mut Canvas::new(&mut buffer, self.language, self.unit))?;
 
-    Ok(content)
+        self.display.draw(&buffer, delay)?;
+        Ok(())
+    }
 }
 
 pub enum ScreenUpdateError {
+    Backlight(display::Error),
+    Display(display::Error),
     Fmt(fmt::Error),
-    HD44870(HD44780Error),
 }
 
 impl From<fmt::Error> for ScreenUpdateError {
@@ -113,31 +137,130 @@ impl From<fmt::Error> for ScreenUpdateError {
     }
 }
 
-impl From<HD44780Error> for ScreenUpdateError {
-    fn from(err: HD44780Error) -> Self {
-        Self::HD44870(err)
+impl From<display::Error> for ScreenUpdateError {
+    fn from(err: display::Error) -> Self {
+        Self::Display(err)
     }
 }
 
-pub struct ScreenConfig<RS, EN, D4, D5, D6, D7>
-where
-    RS: OutputPin,
-    EN: OutputPin,
-    D4: OutputPin,
-    D5: OutputPin,
-    D6: OutputPin,
-    D7: OutputPin,
-{
-    pub rs: RS,
-    pub en: EN,
-    pub d4: D4,
-    pub d5: D5,
-    pub d6: D6,
-    pub d7: D7,
+pub struct ScreenConfig<D: DisplayBackend, BL: Backlight> {
+    pub display: D,
+    pub geometry: Geometry,
+
+    pub language: Language,
+    pub unit: Unit,
+
+    pub backlight: Option<BL>,
+    // Brightness of the dimmed backlight, in percent. Backlights without PWM
+    // stay fully on until they are switched off.
+    pub dimmed_brightness: u8,
+    // Seconds without any input or motion after which the backlight is dimmed
+    // or switched off. `None` disables it.
+    pub dim_after: Option<u32>,
+    pub off_after: Option<u32>,
 }
 
-pub enum Frame {
-    Height(u32),
-    Calibrating,
-    Welcome,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backlight::GpioBacklight,
+        display::{Error, Hd44780Display},
+        hd44780::DataBus,
+        mock::{NoDelay, Pin},
+    };
+    use core::cell::Cell;
+
+    /// Bus counting characters and commands written to the display.
+    struct CountingBus<'a> {
+        characters: &'a Cell<usize>,
+        commands: &'a Cell<usize>,
+    }
+
+    impl DataBus for CountingBus<'_> {
+        fn write_nibble(
+            &mut self,
+            _nibble: u8,
+            _data: bool,
+            _delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn write(
+            &mut self,
+            _byte: u8,
+            data: bool,
+            _delay: &mut (impl DelayMs<u8> + DelayUs<u16>),
+        ) -> Result<(), Error> {
+            let count = if data { self.characters } else { self.commands };
+            count.set(count.get() + 1);
+            Ok(())
+        }
+    }
+
+    struct Text(&'static str);
+
+    impl Render for Text {
+        fn render(&self, canvas: &mut Canvas) -> Result<(), fmt::Error> {
+            canvas.write_str(0, 0, self.0);
+            Ok(())
+        }
+    }
+
+    fn screen<'a>(
+        characters: &'a Cell<usize>,
+        commands: &'a Cell<usize>,
+    ) -> Screen<Hd44780Display<CountingBus<'a>>, GpioBacklight<Pin>> {
+        let bus = CountingBus {
+            characters,
+            commands,
+        };
+        Screen::new(ScreenConfig {
+            display: Hd44780Display::new(bus, &mut NoDelay).ok().unwrap(),
+            geometry: Geometry::Lcd16x2,
+            language: Language::English,
+            unit: Unit::Millimetres { decimals: 2 },
+            backlight: None,
+            dimmed_brightness: 0,
+            dim_after: None,
+            off_after: None,
+        })
+    }
+
+    #[test]
+    fn unchanged_frame_is_not_written() {
+        let (characters, commands) = (Cell::new(0), Cell::new(0));
+        let mut screen = screen(&characters, &commands);
+        screen.update(Text("12.34mm"), &mut NoDelay).ok().unwrap();
+        characters.set(0);
+        commands.set(0);
+
+        screen.update(Text("12.34mm"), &mut NoDelay).ok().unwrap();
+        assert_eq!((characters.get(), commands.get()), (0, 0));
+    }
+
+    #[test]
+    fn changed_character_is_written_once() {
+        let (characters, commands) = (Cell::new(0), Cell::new(0));
+        let mut screen = screen(&characters, &commands);
+        screen.update(Text("12.34mm"), &mut NoDelay).ok().unwrap();
+        characters.set(0);
+        commands.set(0);
+
+        screen.update(Text("12.35mm"), &mut NoDelay).ok().unwrap();
+        // One character, after moving the cursor to it.
+        assert_eq!((characters.get(), commands.get()), (1, 1));
+    }
+
+    #[test]
+    fn first_frame_writes_only_text() {
+        let (characters, commands) = (Cell::new(0), Cell::new(0));
+        let mut screen = screen(&characters, &commands);
+        commands.set(0);
+
+        // Display is cleared at initialization, so spaces aren't written.
+        screen.update(Text("12.34mm"), &mut NoDelay).ok().unwrap();
+        assert_eq!((characters.get(), commands.get()), (7, 1));
+    }
 }
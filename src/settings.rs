@@ -0,0 +1,14 @@
+use crate::{locale::Language, unit::Unit};
+
+/// Settings which can be changed at runtime from the menu.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Settings {
+    /// Interval between signals send to the stepper motor driver, in units
+    /// of the motor's signal delay. The lower it is, the faster lift moves.
+    pub signal_delay: u8,
+    pub motor_steps_per_mm: u32,
+    /// Maximum height in milimetres.
+    pub max_height: u32,
+    pub language: Language,
+    pub unit: Unit,
+}
@@ -0,0 +1,484 @@
+use crate::{fault::Fault, foot_pedal::Direction};
+
+/// What the mill is doing. Position of the lift is known in every state but
+/// `Uncalibrated`, `Resume`, `Homing` and `Fault`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MillState {
+    /// Position is unknown, homing starts on the next tick.
+    Uncalibrated,
+    /// Position saved before the power was switched off can be trusted. The
+    /// operator chooses whether to resume it, or to home the lift.
+    Resume,
+    /// Lift looks for the limit switch at the homed end.
+    Homing(HomingPhase),
+    /// Lift is at the target.
+    Idle,
+    /// Target was changed recently. Lift waits until the operator stops
+    /// turning the knobs, so it doesn't start and stop with every detent.
+    Settling,
+    /// Lift moves to the target.
+    Moving,
+    /// Lift moves as long as the foot pedal is held.
+    Jogging,
+    /// Bit looks for the touch plate to set the work zero.
+    Probing(ProbingPhase),
+    /// Motor is stopped until the fault is acknowledged.
+    Fault(Fault),
+}
+
+impl MillState {
+    pub fn is_calibrated(self) -> bool {
+        !matches!(
+            self,
+            MillState::Uncalibrated
+                | MillState::Resume
+                | MillState::Homing(_)
+                | MillState::Fault(_)
+        )
+    }
+
+    pub fn is_moving(self) -> bool {
+        matches!(
+            self,
+            MillState::Homing(_) | MillState::Moving | MillState::Jogging | MillState::Probing(_)
+        )
+    }
+}
+
+/// End of the lift travel, with a hard limit switch.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Limit {
+    Lower,
+    Upper,
+}
+
+impl Limit {
+    /// Fault of the lift hitting the limit switch outside homing.
+    pub fn fault(self) -> Fault {
+        match self {
+            Limit::Lower => Fault::LowerLimit,
+            Limit::Upper => Fault::UpperLimit,
+        }
+    }
+}
+
+/// Homing finds the switch quickly first, and then slowly again, so the
+/// position at which it triggers is repeatable.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum HomingPhase {
+    /// Lift moves quickly towards the limit switch.
+    FastApproach,
+    /// Lift moves away from the switch.
+    BackOff,
+    /// Lift moves slowly towards the switch.
+    SlowApproach,
+    /// Lift moves away from the switch to the zero height.
+    Offset,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ProbingPhase {
+    /// Lift moves slowly until the bit touches the plate.
+    Approach,
+    /// Lift moves away from the plate.
+    Retract,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Event {
+    /// Main loop tick, with the inputs polled in it.
+    Tick {
+        pedal: Option<Direction>,
+        // Whether target didn't change for long enough to start moving.
+        settled: bool,
+        at_target: bool,
+    },
+    /// Limit switch was reached while homing, or the touch plate while
+    /// probing.
+    SwitchReached,
+    /// Lift moved by the distance of the current homing or probing phase.
+    DistanceTravelled,
+    /// Target was changed with one of the knobs.
+    TargetChanged,
+    /// Home switch was pressed, so lift has to be homed again.
+    HomeSwitch,
+    /// Limit switch was triggered by the lift.
+    LimitSwitch(Limit),
+    Error(Fault),
+    /// Operator acknowledged the fault.
+    Acknowledge,
+    /// Operator chose to resume the saved position.
+    Resume,
+    /// Operator chose to home the lift instead of resuming the position.
+    Rehome,
+    /// Operator started probing the work zero.
+    Probe,
+}
+
+/// What the mill has to do after a transition.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Action {
+    None,
+    /// Start counting homing time.
+    StartHoming,
+    /// Move a bit in the homing phase.
+    Home(HomingPhase),
+    /// Start counting distance of the next homing phase.
+    ResetTravel,
+    /// Lift is at the homed end, and stays there.
+    SetHome,
+    /// Lift is at the saved position.
+    Resume,
+    /// Check the touch plate, and start counting distance of the probing.
+    StartProbing,
+    /// Move a bit in the probing phase.
+    Probe(ProbingPhase),
+    /// Bit touches the plate, set the work zero from its position.
+    SetWorkZero,
+    /// Move a bit towards the target.
+    Step,
+    /// Move the target with the lift, and then move a bit towards it.
+    Jog(Direction),
+    /// Motion has ended.
+    Stop,
+    /// Forget the position.
+    Forget,
+    /// Disable the motor and forget the position.
+    Halt,
+}
+
+/// Returns the next state of the mill and the action it has to take. Events
+/// which make no sense in the current state are ignored.
+pub fn transition(state: MillState, event: Event) -> (MillState, Action) {
+    use MillState::*;
+
+    match (state, event) {
+        (Fault(_), Event::Acknowledge) => (Uncalibrated, Action::None),
+        (Fault(_), _) => (state, Action::None),
+        (_, Event::Error(fault)) => (Fault(fault), Action::Halt),
+        (_, Event::HomeSwitch) => (Uncalibrated, Action::Forget),
+        // Switch is expected to trigger while homing. Anywhere else the
+        // position was lost, so the lift has to be homed again.
+        (Homing(_), Event::LimitSwitch(_)) => (state, Action::None),
+        (_, Event::LimitSwitch(limit)) => (Fault(limit.fault()), Action::Halt),
+
+        (Resume, Event::Resume) => (Idle, Action::Resume),
+        (Resume, Event::Rehome) => (Uncalibrated, Action::None),
+        (Resume, _) => (state, Action::None),
+
+        (Uncalibrated, Event::Tick { .. }) => {
+            (Homing(HomingPhase::FastApproach), Action::StartHoming)
+        }
+        (Homing(phase), Event::Tick { .. }) => (state, Action::Home(phase)),
+        (Homing(HomingPhase::FastApproach), Event::SwitchReached) => {
+            (Homing(HomingPhase::BackOff), Action::ResetTravel)
+        }
+        (Homing(HomingPhase::BackOff), Event::DistanceTravelled) => {
+            (Homing(HomingPhase::SlowApproach), Action::ResetTravel)
+        }
+        (Homing(HomingPhase::SlowApproach), Event::SwitchReached) => {
+            (Homing(HomingPhase::Offset), Action::ResetTravel)
+        }
+        (Homing(HomingPhase::Offset), Event::DistanceTravelled) => (Idle, Action::SetHome),
+        (Uncalibrated, _) | (Homing(_), _) => (state, Action::None),
+
+        (Idle, Event::Probe) => (Probing(ProbingPhase::Approach), Action::StartProbing),
+        (Probing(phase), Event::Tick { .. }) => (state, Action::Probe(phase)),
+        (Probing(ProbingPhase::Approach), Event::SwitchReached) => {
+            (Probing(ProbingPhase::Retract), Action::SetWorkZero)
+        }
+        (Probing(ProbingPhase::Retract), Event::DistanceTravelled) => (Idle, Action::Stop),
+        (Probing(_), _) | (_, Event::Probe) => (state, Action::None),
+
+        (_, Event::TargetChanged) => (Settling, Action::None),
+        (
+            _,
+            Event::Tick {
+                pedal,
+                settled,
+                at_target,
+            },
+        ) => tick(state, pedal, settled, at_target),
+        _ => (state, Action::None),
+    }
+}
+
+/// Transition of a calibrated mill on the main loop tick. Pedal takes
+/// precedence over the target set with the knobs.
+fn tick(
+    state: MillState,
+    pedal: Option<Direction>,
+    settled: bool,
+    at_target: bool,
+) -> (MillState, Action) {
+    use MillState::*;
+
+    if let Some(direction) = pedal {
+        return (Jogging, Action::Jog(direction));
+    }
+
+    match (state, settled, at_target) {
+        (Settling, false, _) => (Settling, Action::None),
+        (Moving, _, true) | (Jogging, _, true) => (Idle, Action::Stop),
+        (_, _, true) => (Idle, Action::None),
+        _ => (Moving, Action::Step),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRECTIONS: [Option<Direction>; 3] = [None, Some(Direction::Up), Some(Direction::Down)];
+
+    fn states() -> [MillState; 14] {
+        [
+            MillState::Uncalibrated,
+            MillState::Resume,
+            MillState::Homing(HomingPhase::FastApproach),
+            MillState::Homing(HomingPhase::BackOff),
+            MillState::Homing(HomingPhase::SlowApproach),
+            MillState::Homing(HomingPhase::Offset),
+            MillState::Idle,
+            MillState::Settling,
+            MillState::Moving,
+            MillState::Jogging,
+            MillState::Probing(ProbingPhase::Approach),
+            MillState::Probing(ProbingPhase::Retract),
+            MillState::Fault(Fault::Motor),
+            MillState::Fault(Fault::LowerLimit),
+        ]
+    }
+
+    fn events() -> [Event; 24] {
+        let mut events = [Event::Probe; 24];
+        let mut index = 0;
+        for pedal in DIRECTIONS.iter() {
+            for settled in [false, true].iter() {
+                for at_target in [false, true].iter() {
+                    events[index] = Event::Tick {
+                        pedal: *pedal,
+                        settled: *settled,
+                        at_target: *at_target,
+                    };
+                    index += 1;
+                }
+            }
+        }
+        let others = [
+            Event::SwitchReached,
+            Event::DistanceTravelled,
+            Event::TargetChanged,
+            Event::HomeSwitch,
+            Event::LimitSwitch(Limit::Lower),
+            Event::LimitSwitch(Limit::Upper),
+            Event::Error(Fault::Display),
+            Event::Acknowledge,
+            Event::Resume,
+            Event::Rehome,
+            Event::Probe,
+        ];
+        events[index..index + others.len()].copy_from_slice(&others);
+        events
+    }
+
+    /// Transitions written down state by state, independently of the order
+    /// of the arms in `transition`.
+    fn expected(state: MillState, event: Event) -> (MillState, Action) {
+        use MillState::*;
+
+        let ignored = (state, Action::None);
+
+        if let Fault(_) = state {
+            return match event {
+                Event::Acknowledge => (Uncalibrated, Action::None),
+                _ => ignored,
+            };
+        }
+
+        match event {
+            Event::Error(fault) => return (Fault(fault), Action::Halt),
+            Event::HomeSwitch => return (Uncalibrated, Action::Forget),
+            Event::LimitSwitch(_) if matches!(state, Homing(_)) => return ignored,
+            Event::LimitSwitch(limit) => return (Fault(limit.fault()), Action::Halt),
+            _ => {}
+        }
+
+        match state {
+            Resume => match event {
+                Event::Resume => (Idle, Action::Resume),
+                Event::Rehome => (Uncalibrated, Action::None),
+                _ => ignored,
+            },
+            Uncalibrated => match event {
+                Event::Tick { .. } => (Homing(HomingPhase::FastApproach), Action::StartHoming),
+                _ => ignored,
+            },
+            Homing(phase) => match (phase, event) {
+                (_, Event::Tick { .. }) => (state, Action::Home(phase)),
+                (HomingPhase::FastApproach, Event::SwitchReached) => {
+                    (Homing(HomingPhase::BackOff), Action::ResetTravel)
+                }
+                (HomingPhase::BackOff, Event::DistanceTravelled) => {
+                    (Homing(HomingPhase::SlowApproach), Action::ResetTravel)
+                }
+                (HomingPhase::SlowApproach, Event::SwitchReached) => {
+                    (Homing(HomingPhase::Offset), Action::ResetTravel)
+                }
+                (HomingPhase::Offset, Event::DistanceTravelled) => (Idle, Action::SetHome),
+                _ => ignored,
+            },
+            Probing(phase) => match (phase, event) {
+                (_, Event::Tick { .. }) => (state, Action::Probe(phase)),
+                (ProbingPhase::Approach, Event::SwitchReached) => {
+                    (Probing(ProbingPhase::Retract), Action::SetWorkZero)
+                }
+                (ProbingPhase::Retract, Event::DistanceTravelled) => (Idle, Action::Stop),
+                _ => ignored,
+            },
+            Idle | Settling | Moving | Jogging => match event {
+                Event::Probe if state == Idle => {
+                    (Probing(ProbingPhase::Approach), Action::StartProbing)
+                }
+                Event::TargetChanged => (Settling, Action::None),
+                Event::Tick {
+                    pedal: Some(direction),
+                    ..
+                } => (Jogging, Action::Jog(direction)),
+                Event::Tick { settled: false, .. } if state == Settling => ignored,
+                Event::Tick {
+                    at_target: true, ..
+                } if state == Moving || state == Jogging => (Idle, Action::Stop),
+                Event::Tick {
+                    at_target: true, ..
+                } => (Idle, Action::None),
+                Event::Tick { .. } => (Moving, Action::Step),
+                _ => ignored,
+            },
+            Fault(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn every_event_in_every_state() {
+        for state in states().iter() {
+            for event in events().iter() {
+                assert_eq!(
+                    transition(*state, *event),
+                    expected(*state, *event),
+                    "{:?} on {:?}",
+                    event,
+                    state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn faults_wait_for_acknowledge() {
+        let state = MillState::Fault(Fault::Motor);
+        for event in events()
+            .iter()
+            .filter(|event| **event != Event::Acknowledge)
+        {
+            assert_eq!(transition(state, *event), (state, Action::None));
+        }
+        assert_eq!(
+            transition(state, Event::Acknowledge),
+            (MillState::Uncalibrated, Action::None)
+        );
+    }
+
+    #[test]
+    fn limit_switch_faults_outside_homing() {
+        let homing = MillState::Homing(HomingPhase::SlowApproach);
+        assert_eq!(
+            transition(homing, Event::LimitSwitch(Limit::Lower)),
+            (homing, Action::None)
+        );
+
+        for state in [
+            MillState::Resume,
+            MillState::Idle,
+            MillState::Moving,
+            MillState::Probing(ProbingPhase::Approach),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                transition(*state, Event::LimitSwitch(Limit::Upper)),
+                (MillState::Fault(Fault::UpperLimit), Action::Halt)
+            );
+        }
+    }
+
+    #[test]
+    fn homing_runs_through_its_phases() {
+        let tick = Event::Tick {
+            pedal: None,
+            settled: true,
+            at_target: true,
+        };
+        let mut state = MillState::Uncalibrated;
+        let mut actions = [Action::None; 5];
+        let events = [
+            tick,
+            Event::SwitchReached,
+            Event::DistanceTravelled,
+            Event::SwitchReached,
+            Event::DistanceTravelled,
+        ];
+        for (event, action) in events.iter().zip(actions.iter_mut()) {
+            let (next, next_action) = transition(state, *event);
+            state = next;
+            *action = next_action;
+        }
+
+        assert_eq!(state, MillState::Idle);
+        assert_eq!(
+            actions,
+            [
+                Action::StartHoming,
+                Action::ResetTravel,
+                Action::ResetTravel,
+                Action::ResetTravel,
+                Action::SetHome,
+            ]
+        );
+    }
+
+    #[test]
+    fn probing_sets_work_zero_and_retracts() {
+        let tick = Event::Tick {
+            pedal: None,
+            settled: true,
+            at_target: true,
+        };
+        let mut state = MillState::Idle;
+        let mut actions = [Action::None; 5];
+        let events = [
+            Event::Probe,
+            tick,
+            Event::SwitchReached,
+            tick,
+            Event::DistanceTravelled,
+        ];
+        for (event, action) in events.iter().zip(actions.iter_mut()) {
+            let (next, next_action) = transition(state, *event);
+            state = next;
+            *action = next_action;
+        }
+
+        assert_eq!(state, MillState::Idle);
+        assert_eq!(
+            actions,
+            [
+                Action::StartProbing,
+                Action::Probe(ProbingPhase::Approach),
+                Action::SetWorkZero,
+                Action::Probe(ProbingPhase::Retract),
+                Action::Stop,
+            ]
+        );
+    }
+}
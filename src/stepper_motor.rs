@@ -80,6 +80,14 @@ where
         Ok(self)
     }
 
+    pub fn signal_delay(&self) -> Duration<DUR> {
+        self.signal_delay
+    }
+
+    pub fn set_signal_delay(&mut self, signal_delay: Duration<DUR>) {
+        self.signal_delay = signal_delay;
+    }
+
     pub fn rotate_clockwise(
         &mut self,
         steps: u32,
@@ -175,6 +183,14 @@ impl<T: Copy> Duration<T> {
             Duration::Us(dur) => delay.delay_us(dur),
         }
     }
+
+    /// Returns duration of the same unit, but with different value.
+    pub fn with_value(self, value: T) -> Self {
+        match self {
+            Duration::Ms(_) => Duration::Ms(value),
+            Duration::Us(_) => Duration::Us(value),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
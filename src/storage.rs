@@ -0,0 +1,378 @@
+use crate::{locale::Language, preset::PRESET_COUNT, settings::Settings, unit::Unit};
+use embedded_storage::nor_flash::NorFlash;
+
+// Version of the record layout. Records of other versions are ignored, so
+// the mill starts with the compiled in settings after it changes.
+const VERSION: u8 = 2;
+
+// Sequence number, version, settings, preset heights, position and CRC.
+const RECORD_SIZE: usize = 4 + 1 + 13 + 4 * PRESET_COUNT + 4 + 4;
+const POSITION_OFFSET: usize = 18 + 4 * PRESET_COUNT;
+// Records are written in slots of this size, which has to be a multiple of
+// the read and write size of the flash.
+const SLOT_SIZE: usize = 64;
+
+// Byte of the slot cleared when the lift starts moving. It's outside of the
+// record, so it can be programmed without writing a new one.
+const MOVING_OFFSET: usize = SLOT_SIZE - 1;
+
+// Sequence number of an erased slot.
+const ERASED: u32 = u32::MAX;
+
+/// Settings and presets saved in the flash.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Record {
+    pub settings: Settings,
+    /// Preset heights in motor steps.
+    pub presets: [Option<u32>; PRESET_COUNT],
+    /// Height of the lift in motor steps, saved when it stops. Record is
+    /// marked as soon as the lift starts moving, so it's `None` after the
+    /// power was switched off mid-move.
+    pub position: Option<u32>,
+}
+
+/// Keeps records in a region of NOR flash. Every record is written to the
+/// next free slot, and the one with the highest sequence number is the
+/// current one, so a page is erased only after all of its slots were used.
+/// Torn writes fail the CRC check and are skipped.
+///
+/// The region has to span at least two erase pages, so the current record is
+/// kept in one of them while the next one is erased.
+pub struct Storage<F: NorFlash> {
+    flash: F,
+    offset: u32,
+    slots: u32,
+    // Slot and sequence number of the current record, `None` until the
+    // region was scanned.
+    current: Option<(u32, u32)>,
+}
+
+impl<F: NorFlash> Storage<F> {
+    /// Region starts at `offset` and has `size` bytes. Both have to be
+    /// multiples of the erase size of the flash.
+    pub fn new(flash: F, offset: u32, size: u32) -> Self {
+        Self {
+            flash,
+            offset,
+            slots: size / SLOT_SIZE as u32,
+            current: None,
+        }
+    }
+
+    /// Returns the current record, or `None` when there is none with the
+    /// current version.
+    pub fn load(&mut self) -> Result<Option<Record>, F::Error> {
+        let mut current = None;
+        let mut record = None;
+
+        for slot in 0..self.slots {
+            let bytes = self.read_slot(slot)?;
+            let sequence = read_u32(&bytes, 0);
+            if sequence == ERASED || read_u32(&bytes, RECORD_SIZE - 4) != crc(&bytes) {
+                continue;
+            }
+
+            if current.map_or(true, |(_, current)| sequence > current) {
+                current = Some((slot, sequence));
+                record = decode(&bytes);
+            }
+        }
+
+        self.current = current;
+        Ok(record)
+    }
+
+    pub fn save(&mut self, record: &Record) -> Result<(), F::Error> {
+        if self.current.is_none() {
+            self.load()?;
+        }
+
+        let (mut slot, sequence) = match self.current {
+            Some((slot, sequence)) => ((slot + 1) % self.slots, sequence.wrapping_add(1)),
+            None => (0, 0),
+        };
+
+        // Page is erased when it's entered, so following slots are free.
+        // Slots left over from torn writes are skipped.
+        loop {
+            let offset = self.offset + slot * SLOT_SIZE as u32;
+            if offset % F::ERASE_SIZE as u32 == 0 {
+                self.flash.erase(offset, offset + F::ERASE_SIZE as u32)?;
+                break;
+            }
+            if self.read_slot(slot)?.iter().all(|byte| *byte == 0xff) {
+                break;
+            }
+            slot = (slot + 1) % self.slots;
+        }
+
+        let bytes = encode(record, sequence);
+        self.flash
+            .write(self.offset + slot * SLOT_SIZE as u32, &bytes)?;
+        self.current = Some((slot, sequence));
+        Ok(())
+    }
+
+    /// Marks the current record as saved before the lift started moving, so
+    /// it's loaded without the position. Only a byte of its slot is
+    /// programmed, so unlike saving a record it never erases a page.
+    pub fn mark_moving(&mut self) -> Result<(), F::Error> {
+        if self.current.is_none() {
+            self.load()?;
+        }
+
+        if let Some((slot, _)) = self.current {
+            let end = self.offset + (slot + 1) * SLOT_SIZE as u32;
+            self.flash
+                .write(end - F::WRITE_SIZE as u32, &[0; SLOT_SIZE][..F::WRITE_SIZE])?;
+        }
+        Ok(())
+    }
+
+    fn read_slot(&mut self, slot: u32) -> Result<[u8; SLOT_SIZE], F::Error> {
+        let mut bytes = [0; SLOT_SIZE];
+        self.flash
+            .read(self.offset + slot * SLOT_SIZE as u32, &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Record in the slot layout. Bytes after the record are left erased.
+fn encode(record: &Record, sequence: u32) -> [u8; SLOT_SIZE] {
+    let settings = &record.settings;
+    let mut bytes = [0xff; SLOT_SIZE];
+
+    write_u32(&mut bytes, 0, sequence);
+    bytes[4] = VERSION;
+    bytes[5] = settings.signal_delay;
+    write_u32(&mut bytes, 6, settings.motor_steps_per_mm);
+    write_u32(&mut bytes, 10, settings.max_height);
+    bytes[14] = match settings.language {
+        Language::Polish => 0,
+        Language::English => 1,
+        Language::German => 2,
+    };
+    let (unit, value) = match settings.unit {
+        Unit::Millimetres { decimals } => (0, decimals as u16),
+        Unit::Inches { decimals } => (1, decimals as u16),
+        Unit::FractionalInches { denominator } => (2, denominator),
+    };
+    bytes[15] = unit;
+    bytes[16..18].copy_from_slice(&value.to_le_bytes());
+    for (index, preset) in record.presets.iter().enumerate() {
+        write_u32(&mut bytes, 18 + 4 * index, preset.unwrap_or(ERASED));
+    }
+    write_u32(
+        &mut bytes,
+        POSITION_OFFSET,
+        record.position.unwrap_or(ERASED),
+    );
+
+    let crc = crc(&bytes);
+    write_u32(&mut bytes, RECORD_SIZE - 4, crc);
+    bytes
+}
+
+/// Returns `None` for records of other versions, or with unknown values.
+fn decode(bytes: &[u8; SLOT_SIZE]) -> Option<Record> {
+    if bytes[4] != VERSION {
+        return None;
+    }
+
+    let language = match bytes[14] {
+        0 => Language::Polish,
+        1 => Language::English,
+        2 => Language::German,
+        _ => return None,
+    };
+    let value = u16::from_le_bytes([bytes[16], bytes[17]]);
+    let unit = match bytes[15] {
+        0 => Unit::Millimetres {
+            decimals: value as u8,
+        },
+        1 => Unit::Inches {
+            decimals: value as u8,
+        },
+        2 => Unit::FractionalInches { denominator: value },
+        _ => return None,
+    };
+
+    let mut presets = [None; PRESET_COUNT];
+    for (index, preset) in presets.iter_mut().enumerate() {
+        let height = read_u32(bytes, 18 + 4 * index);
+        if height != ERASED {
+            *preset = Some(height);
+        }
+    }
+
+    let position = match read_u32(bytes, POSITION_OFFSET) {
+        _ if bytes[MOVING_OFFSET] != 0xff => None,
+        ERASED => None,
+        position => Some(position),
+    };
+
+    Some(Record {
+        settings: Settings {
+            signal_delay: bytes[5],
+            motor_steps_per_mm: read_u32(bytes, 6),
+            max_height: read_u32(bytes, 10),
+            language,
+            unit,
+        },
+        presets,
+        position,
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// CRC-32 of the record without its CRC.
+fn crc(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in &bytes[..RECORD_SIZE - 4] {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{RamFlash, FLASH_SIZE as SIZE, PAGE_SIZE};
+
+    fn record(number: u32) -> Record {
+        Record {
+            settings: Settings {
+                signal_delay: number as u8,
+                motor_steps_per_mm: 200 + number,
+                max_height: 48,
+                language: Language::German,
+                unit: Unit::FractionalInches { denominator: 64 },
+            },
+            presets: [Some(number), None, Some(7), None],
+            position: Some(number * 10),
+        }
+    }
+
+    #[test]
+    fn empty_region_has_no_record() {
+        let mut storage = Storage::new(RamFlash::new(), 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(None));
+    }
+
+    #[test]
+    fn saved_record_is_loaded_after_restart() {
+        let mut flash = RamFlash::new();
+        Storage::new(&mut flash, 0, SIZE as u32)
+            .save(&record(1))
+            .unwrap();
+
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(Some(record(1))));
+    }
+
+    #[test]
+    fn saves_wrap_around_the_region() {
+        let mut flash = RamFlash::new();
+        let slots = SIZE / SLOT_SIZE;
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        for number in 0..3 * slots as u32 {
+            storage.save(&record(number)).unwrap();
+        }
+        assert_eq!(storage.load(), Ok(Some(record(3 * slots as u32 - 1))));
+
+        // Every page is erased once per pass over the region.
+        assert_eq!(flash.erases, 3 * SIZE / PAGE_SIZE);
+    }
+
+    #[test]
+    fn erase_keeps_the_record_in_the_other_pages() {
+        let mut flash = RamFlash::new();
+        let slots_per_page = (PAGE_SIZE / SLOT_SIZE) as u32;
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        for number in 0..slots_per_page {
+            storage.save(&record(number)).unwrap();
+        }
+        // The next save erases the second page before writing to it.
+        flash.torn_write = Some(0);
+
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        storage.save(&record(slots_per_page)).unwrap();
+        assert_eq!(storage.load(), Ok(Some(record(slots_per_page - 1))));
+    }
+
+    #[test]
+    fn torn_write_is_skipped() {
+        let mut flash = RamFlash::new();
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        storage.save(&record(1)).unwrap();
+        flash.torn_write = Some(RECORD_SIZE / 2);
+        Storage::new(&mut flash, 0, SIZE as u32)
+            .save(&record(2))
+            .unwrap();
+
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(Some(record(1))));
+
+        // Slot of the torn write isn't reused.
+        storage.save(&record(3)).unwrap();
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(Some(record(3))));
+    }
+
+    #[test]
+    fn position_is_dropped_after_lift_started_moving() {
+        let mut flash = RamFlash::new();
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        storage.save(&record(1)).unwrap();
+        storage.save(&record(2)).unwrap();
+        storage.mark_moving().unwrap();
+        assert_eq!(flash.erases, 1);
+
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        let moved = Record {
+            position: None,
+            ..record(2)
+        };
+        assert_eq!(storage.load(), Ok(Some(moved)));
+
+        // Next record has its position again.
+        storage.save(&record(3)).unwrap();
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(Some(record(3))));
+    }
+
+    #[test]
+    fn other_version_is_ignored() {
+        let mut flash = RamFlash::new();
+        Storage::new(&mut flash, 0, SIZE as u32)
+            .save(&record(1))
+            .unwrap();
+
+        let mut slot = [0xff; SLOT_SIZE];
+        slot.copy_from_slice(&flash.bytes[..SLOT_SIZE]);
+        slot[4] = VERSION + 1;
+        let crc = crc(&slot);
+        write_u32(&mut slot, RECORD_SIZE - 4, crc);
+        flash.erase(0, PAGE_SIZE as u32).unwrap();
+        flash.write(0, &slot).unwrap();
+
+        let mut storage = Storage::new(&mut flash, 0, SIZE as u32);
+        assert_eq!(storage.load(), Ok(None));
+    }
+}
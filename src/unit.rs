@@ -0,0 +1,224 @@
+use arrayvec::ArrayString;
+use core::fmt::{self, Write};
+
+const MICROMETRES_PER_INCH: u64 = 25_400;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Unit {
+    /// Up to 3 decimal places.
+    Millimetres { decimals: u8 },
+    /// Up to 4 decimal places.
+    Inches { decimals: u8 },
+    /// Fraction of an inch, like 1 3/64". Denominator should be a power of
+    /// two.
+    FractionalInches { denominator: u16 },
+}
+
+impl Unit {
+    /// Same unit with one decimal place less, or a twice bigger fraction.
+    /// `None` for whole units.
+    pub fn coarser(self) -> Option<Unit> {
+        match self {
+            Unit::Millimetres { decimals: 0 }
+            | Unit::Inches { decimals: 0 }
+            | Unit::FractionalInches { denominator: 0..=1 } => None,
+            Unit::Millimetres { decimals } => Some(Unit::Millimetres {
+                decimals: decimals.min(3) - 1,
+            }),
+            Unit::Inches { decimals } => Some(Unit::Inches {
+                decimals: decimals.min(4) - 1,
+            }),
+            Unit::FractionalInches { denominator } => Some(Unit::FractionalInches {
+                denominator: denominator / 2,
+            }),
+        }
+    }
+}
+
+/// Formats height given in micrometres, rounding it to the precision of the
+/// unit. Heights in work coordinates are negative below the work zero.
+pub fn format_height(micrometres: i32, unit: Unit) -> Result<ArrayString<[u8; 16]>, fmt::Error> {
+    let mut content = ArrayString::<[_; 16]>::new();
+    if micrometres < 0 {
+        content.push('-');
+    }
+    let micrometres = (micrometres as i64).abs() as u64;
+
+    match unit {
+        Unit::Millimetres { decimals: 0 } => {
+            write!(content, "{:02}mm", round_div(micrometres, 1000))?;
+        }
+        Unit::Millimetres { decimals } => {
+            let decimals = decimals.min(3);
+            let scale = 10u64.pow(decimals as u32);
+            let value = round_div(micrometres * scale, 1000);
+            write_decimal(&mut content, value, scale, decimals)?;
+            content.try_push_str("mm").map_err(|_| fmt::Error)?;
+        }
+        Unit::Inches { decimals } => {
+            let decimals = decimals.min(4);
+            let scale = 10u64.pow(decimals as u32);
+            let value = round_div(micrometres * scale, MICROMETRES_PER_INCH);
+            write_decimal(&mut content, value, scale, decimals)?;
+            content.try_push('"').map_err(|_| fmt::Error)?;
+        }
+        Unit::FractionalInches { denominator } => {
+            let mut denominator = denominator.max(1) as u64;
+            let value = round_div(micrometres * denominator, MICROMETRES_PER_INCH);
+            let whole = value / denominator;
+            let mut numerator = value % denominator;
+
+            while numerator != 0 && numerator % 2 == 0 && denominator % 2 == 0 {
+                numerator /= 2;
+                denominator /= 2;
+            }
+
+            if numerator == 0 {
+                write!(content, "{}\"", whole)?;
+            } else if whole == 0 {
+                write!(content, "{}/{}\"", numerator, denominator)?;
+            } else {
+                write!(content, "{} {}/{}\"", whole, numerator, denominator)?;
+            }
+        }
+    }
+
+    Ok(content)
+}
+
+/// Formats the smallest height difference shown in the unit, like "0.01mm".
+pub fn format_resolution(unit: Unit) -> Result<ArrayString<[u8; 16]>, fmt::Error> {
+    let mut content = ArrayString::<[_; 16]>::new();
+
+    match unit {
+        Unit::Millimetres { decimals } => {
+            let decimals = decimals.min(3);
+            write_decimal(&mut content, 1, 10u64.pow(decimals as u32), decimals)?;
+            content.try_push_str("mm").map_err(|_| fmt::Error)?;
+        }
+        Unit::Inches { decimals } => {
+            let decimals = decimals.min(4);
+            write_decimal(&mut content, 1, 10u64.pow(decimals as u32), decimals)?;
+            content.try_push('"').map_err(|_| fmt::Error)?;
+        }
+        Unit::FractionalInches { denominator } => {
+            write!(content, "1/{}\"", denominator.max(1))?;
+        }
+    }
+
+    Ok(content)
+}
+
+/// Formats jog increment given in hundredths of a millimetre, like "0.1mm".
+/// Increments are metric in every unit, as the encoder jogs by millimetres,
+/// so they are always shown in millimetres.
+pub fn format_jog_increment(hundredths: u32) -> Result<ArrayString<[u8; 16]>, fmt::Error> {
+    let mut content = ArrayString::<[_; 16]>::new();
+    let hundredths = hundredths as u64;
+
+    if hundredths % 100 == 0 {
+        write_decimal(&mut content, hundredths / 100, 1, 0)?;
+    } else if hundredths % 10 == 0 {
+        write_decimal(&mut content, hundredths / 10, 10, 1)?;
+    } else {
+        write_decimal(&mut content, hundredths, 100, 2)?;
+    }
+    content.try_push_str("mm").map_err(|_| fmt::Error)?;
+
+    Ok(content)
+}
+
+fn write_decimal(
+    content: &mut ArrayString<[u8; 16]>,
+    value: u64,
+    scale: u64,
+    decimals: u8,
+) -> fmt::Result {
+    if decimals == 0 {
+        write!(content, "{}", value)
+    } else {
+        write!(
+            content,
+            "{}.{:0width$}",
+            value / scale,
+            value % scale,
+            width = decimals as usize
+        )
+    }
+}
+
+fn round_div(dividend: u64, divisor: u64) -> u64 {
+    (dividend + divisor / 2) / divisor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn height(micrometres: i32, unit: Unit) -> ArrayString<[u8; 16]> {
+        format_height(micrometres, unit).unwrap()
+    }
+
+    #[test]
+    fn millimetres_round_half_a_hundredth_up() {
+        let unit = Unit::Millimetres { decimals: 2 };
+        assert_eq!(height(5, unit).as_str(), "0.01mm");
+        assert_eq!(height(4, unit).as_str(), "0.00mm");
+        assert_eq!(height(12_345, unit).as_str(), "12.35mm");
+        assert_eq!(
+            height(2_000, Unit::Millimetres { decimals: 0 }).as_str(),
+            "02mm"
+        );
+    }
+
+    #[test]
+    fn heights_below_work_zero_are_negative() {
+        let unit = Unit::Millimetres { decimals: 2 };
+        assert_eq!(height(-5, unit).as_str(), "-0.01mm");
+        assert_eq!(height(-12_345, unit).as_str(), "-12.35mm");
+        let unit = Unit::FractionalInches { denominator: 64 };
+        assert_eq!(height(-12_700, unit).as_str(), "-1/2\"");
+    }
+
+    #[test]
+    fn inches_round_to_their_decimals() {
+        let unit = Unit::Inches { decimals: 4 };
+        assert_eq!(height(25_400, unit).as_str(), "1.0000\"");
+        assert_eq!(height(13, unit).as_str(), "0.0005\"");
+        assert_eq!(height(1, unit).as_str(), "0.0000\"");
+        assert_eq!(
+            height(1_000, Unit::Inches { decimals: 3 }).as_str(),
+            "0.039\""
+        );
+    }
+
+    #[test]
+    fn fractional_inches_are_reduced() {
+        let unit = Unit::FractionalInches { denominator: 64 };
+        assert_eq!(height(25_400, unit).as_str(), "1\"");
+        assert_eq!(height(12_700, unit).as_str(), "1/2\"");
+        assert_eq!(height(26_591, unit).as_str(), "1 3/64\"");
+    }
+
+    #[test]
+    fn jog_increment_drops_trailing_zeros() {
+        assert_eq!(format_jog_increment(1).unwrap().as_str(), "0.01mm");
+        assert_eq!(format_jog_increment(10).unwrap().as_str(), "0.1mm");
+        assert_eq!(format_jog_increment(100).unwrap().as_str(), "1mm");
+        assert_eq!(format_jog_increment(1000).unwrap().as_str(), "10mm");
+        assert_eq!(format_jog_increment(125).unwrap().as_str(), "1.25mm");
+    }
+
+    #[test]
+    fn coarser_unit_drops_one_decimal_place() {
+        let unit = Unit::Millimetres { decimals: 2 }.coarser().unwrap();
+        assert_eq!(format_height(123_456, unit).unwrap().as_str(), "123.5mm");
+        let unit = unit.coarser().unwrap();
+        assert_eq!(format_height(123_456, unit).unwrap().as_str(), "123mm");
+        assert_eq!(unit.coarser(), None);
+        assert_eq!(
+            Unit::FractionalInches { denominator: 64 }.coarser(),
+            Some(Unit::FractionalInches { denominator: 32 })
+        );
+    }
+}